@@ -2,4 +2,13 @@
 
 const cli = require("./index");
 
-cli.main();
+try {
+  cli.main(process.argv.slice(2));
+} catch (error) {
+  // Errors of the cli are already reported, see `main` in `src/lib.rs`.
+  if (!error.code?.startsWith("ERR_TADA_")) {
+    console.error(error);
+  }
+
+  process.exitCode = 1;
+}
@@ -0,0 +1,263 @@
+#![deny(clippy::all)]
+
+use std::path::PathBuf;
+
+use clap::error::ErrorKind;
+use clap::{Parser, Subcommand};
+
+use super::error::{Result, TadaError};
+use super::utils::config::PACKAGE_MANAGERS;
+use super::utils::conflict::CONFLICT_POLICIES;
+use super::utils::name::validate_package_name;
+
+pub const DEFAULT_REGISTRY: &str = "https://registry.npmjs.org";
+
+#[derive(Parser, Debug)]
+#[command(name = "create-tada-app", about, long_about = None)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Resolve the merged dependencies against the registry instead of
+    /// keeping the ranges declared by the template and addons.
+    #[arg(long)]
+    pub latest: bool,
+
+    /// Dist-tag used by `--latest` for packages declared with a range.
+    #[arg(long, default_value = "latest", requires = "latest")]
+    pub dist_tag: String,
+
+    /// Also resolve exact versions and prerelease ranges with `--latest`,
+    /// which keep their declared version by default.
+    #[arg(long, requires = "latest")]
+    pub latest_pinned: bool,
+
+    /// Registry queried by `--latest`.
+    #[arg(long, env = "TADA_APP_REGISTRY", default_value = DEFAULT_REGISTRY)]
+    pub registry: String,
+
+    /// Seconds to wait for the registry before falling back to the
+    /// declared ranges.
+    #[arg(long, default_value_t = 5)]
+    pub registry_timeout: u64,
+
+    /// Catalog root, before the ones from `TADA_APP` and the config files.
+    /// Either a path, a git repository (`git+<url>#<ref>`) or a
+    /// `.tar.gz`/`.zip` archive. Can be repeated.
+    #[arg(long = "root", value_name = "SOURCE")]
+    pub roots: Vec<String>,
+
+    /// Only use the cached copy of git and remote catalog roots.
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Seconds to wait for a remote archive root to download before falling
+    /// back to its cached copy.
+    #[arg(long, default_value_t = 60)]
+    pub fetch_timeout: u64,
+
+    /// Package manager used to install the dependencies.
+    #[arg(long, value_parser = PACKAGE_MANAGERS)]
+    pub package_manager: Option<String>,
+
+    /// Install the dependencies without asking.
+    #[arg(long, overrides_with = "no_install")]
+    pub install: bool,
+
+    /// Skip installing the dependencies without asking.
+    #[arg(long, overrides_with = "install")]
+    pub no_install: bool,
+
+    /// Initialize a git repository with the generated app without asking.
+    #[arg(long, overrides_with = "no_git")]
+    pub git: bool,
+
+    /// Skip initializing a git repository without asking.
+    #[arg(long, overrides_with = "git")]
+    pub no_git: bool,
+
+    /// Commit the template, then every addon, separately instead of in a
+    /// single initial commit.
+    #[arg(long)]
+    pub commit_per_addon: bool,
+
+    /// Skip the hooks of the template and addons.
+    #[arg(long, conflicts_with = "trust_hooks")]
+    pub no_hooks: bool,
+
+    /// Run the hooks from git and archive catalog roots without asking.
+    #[arg(long)]
+    pub trust_hooks: bool,
+
+    /// What to do when an addon would replace an existing file: follow the
+    /// mode of the entry, ask for every file, keep the existing file, take
+    /// the addon one, or merge both with conflict markers. Markers fail on
+    /// binary files. On upgrade, settles the files changed on both sides.
+    #[arg(long, global = true, value_parser = CONFLICT_POLICIES, default_value = "mode")]
+    pub on_conflict: String,
+
+    /// Generate into a non-empty directory without asking, over its files.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Name in the `package.json` of the app, derived from the directory
+    /// name by default.
+    #[arg(long, value_name = "NAME", value_parser = parse_package_name)]
+    pub package_name: Option<String>,
+
+    /// Preset from the catalog `presets` directory to start from.
+    #[arg(long, value_name = "NAME")]
+    pub preset: Option<String>,
+
+    /// Addon to apply without asking. Can be repeated.
+    #[arg(long = "addon", value_name = "NAME")]
+    pub addons: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Check the templates, addons and presets of a catalog.
+    Validate {
+        /// Catalog root to check instead of the configured ones.
+        root: Option<String>,
+    },
+    /// Generate every compatible template and addon combination into a
+    /// temporary directory and check that it builds.
+    Test {
+        /// Only test this template. Can be repeated.
+        #[arg(long = "template", value_name = "NAME")]
+        templates: Vec<String>,
+
+        /// Only test the combinations including this addon. Can be repeated.
+        #[arg(long = "addon", value_name = "NAME")]
+        addons: Vec<String>,
+
+        /// Largest number of addons applied together.
+        #[arg(long, default_value_t = 1)]
+        max_addons: usize,
+
+        /// Install the dependencies of every generated app.
+        #[arg(long)]
+        install: bool,
+
+        /// Script run after installing, `build` by default. Can be repeated.
+        #[arg(long = "script", value_name = "NAME", requires = "install")]
+        scripts: Vec<String>,
+
+        /// Keep the generated apps instead of removing them.
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Compare the output of the catalog scenarios with their snapshots.
+    Snapshot {
+        /// Fail when a fresh generation differs from its snapshot. The
+        /// default, the flag only makes it explicit.
+        #[arg(long, conflicts_with = "update")]
+        check: bool,
+
+        /// Write the fresh generations as the new snapshots.
+        #[arg(long)]
+        update: bool,
+
+        /// Only this scenario. Can be repeated.
+        #[arg(long = "scenario", value_name = "NAME")]
+        scenarios: Vec<String>,
+    },
+    /// Run a scratch app with addons under development, applying every
+    /// change made to the addons.
+    Dev {
+        /// Template of the scratch app.
+        #[arg(long, value_name = "NAME")]
+        template: String,
+
+        /// Addon, as a name or a path to its directory. Can be repeated.
+        #[arg(long = "addon", value_name = "NAME_OR_PATH", required = true)]
+        addons: Vec<String>,
+
+        /// Script run in the scratch app.
+        #[arg(long, default_value = "dev")]
+        script: String,
+
+        /// Where to generate the scratch app, in the cache by default.
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+    /// Merge the changes made to the template and addons since the app was
+    /// generated into it.
+    Upgrade {
+        /// Directory of the app, the current one by default.
+        dir: Option<PathBuf>,
+
+        /// Only show what would change.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+impl Args {
+    pub fn install(&self) -> Option<bool> {
+        match (self.install, self.no_install) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn git(&self) -> Option<bool> {
+        match (self.git, self.no_git) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+fn parse_package_name(name: &str) -> Result<String, String> {
+    validate_package_name(name).map(|_| name.to_string())
+}
+
+/// `None` when `--help` or `--version` was printed instead. Clap errors are
+/// returned rather than exiting, which would end the napi host too.
+pub fn parse_args(args: Vec<String>) -> Result<Option<Args>> {
+    let args = std::iter::once("create-tada-app".to_string()).chain(args);
+
+    match Args::try_parse_from(args) {
+        Ok(args) => Ok(Some(args)),
+        Err(e) if matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) => {
+            e.print()
+                .map_err(TadaError::fs("Error printing the usage message"))?;
+            Ok(None)
+        }
+        Err(e) => Err(TadaError::Args(
+            e.render().to_string().trim_end().to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Option<Args>> {
+        parse_args(args.iter().map(|x| x.to_string()).collect())
+    }
+
+    #[test]
+    fn arguments_are_parsed() {
+        let parsed = args(&["--no-install", "--root", "./catalog"])
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(parsed.install(), Some(false));
+        assert_eq!(parsed.roots, ["./catalog"]);
+    }
+
+    #[test]
+    fn invalid_arguments_are_coded_errors() {
+        let error = args(&["--on-conflict", "nope"]).unwrap_err();
+
+        assert_eq!(error.code(), "ERR_TADA_ARGS");
+        assert!(error.to_string().contains("--on-conflict"));
+        assert!(args(&["--no-such-flag"]).is_err());
+    }
+}
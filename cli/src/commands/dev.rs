@@ -0,0 +1,387 @@
+#![deny(clippy::all)]
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use cliclack::{log, outro};
+use notify::{RecursiveMode, Watcher};
+
+use crate::error::{Result, TadaError};
+use crate::generate::{generate, ignore_patterns, Manifest};
+use crate::start_spinner;
+use crate::utils::conflict::ConflictPolicy;
+use crate::utils::diagnostic::JsonDiagnostic;
+use crate::utils::fs::{
+    get_filtered_addons, get_templates, list_files, Details, Shadowed, TADA_JSON,
+};
+use crate::utils::interrupt::Interrupt;
+use crate::utils::pm::{self, install_dependencies};
+use crate::utils::source::cache_dir;
+
+/// Marks a directory as a scratch app, which can be cleared on the next run.
+const MARKER: &str = ".tada-dev";
+
+/// Changes closer than this are applied together.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+pub struct DevOptions {
+    pub template: String,
+    pub addons: Vec<String>,
+    pub script: String,
+    pub dir: Option<PathBuf>,
+    pub package_manager: String,
+}
+
+/// An addon given by path is used as is, otherwise it is looked up by name
+/// among the addons compatible with the template.
+fn resolve_addons(roots: &[PathBuf], template: &Details, specs: &[String]) -> Result<Vec<Details>> {
+    let mut shadowed: Vec<Shadowed> = Vec::new();
+    let mut invalid_addons: Vec<JsonDiagnostic> = Vec::new();
+    let mut compatible_addons: BTreeMap<String, Details> = BTreeMap::new();
+    for root in roots {
+        get_filtered_addons(
+            root.as_os_str(),
+            template.name.clone(),
+            &mut compatible_addons,
+            &mut shadowed,
+            &mut invalid_addons,
+        )?;
+    }
+
+    let mut addons: Vec<Details> = Vec::new();
+
+    for spec in specs {
+        let path = Path::new(spec);
+
+        if path.join(TADA_JSON).is_file() {
+            let path = path
+                .canonicalize()
+                .map_err(TadaError::fs(format!("Error reading addon {:?}", path)))?;
+
+            addons.push(Details {
+                name: path
+                    .file_name()
+                    .map(|x| x.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                root: path.parent().map(OsString::from).unwrap_or_default(),
+                path: path.into_os_string(),
+            });
+            continue;
+        }
+
+        match compatible_addons.get(spec) {
+            Some(addon) => addons.push(addon.clone()),
+            None => {
+                return Err(TadaError::Catalog(format!(
+                    "addon `{}` not found or not compatible with template `{}`",
+                    spec, template.name
+                )))
+            }
+        }
+    }
+
+    Ok(addons)
+}
+
+/// Removes everything from the scratch app but its dependencies, so they
+/// are not installed again on every run.
+fn clear_scratch_app(app_path: &Path) -> Result<()> {
+    if !app_path.is_dir() {
+        return Ok(());
+    }
+
+    let error = TadaError::fs(format!("Error clearing the scratch app: {:?}", app_path));
+
+    let result = (|| -> std::io::Result<bool> {
+        if fs::read_dir(app_path)?.next().is_some() && !app_path.join(MARKER).is_file() {
+            return Ok(false);
+        }
+
+        for entry in fs::read_dir(app_path)?.flatten() {
+            let path = entry.path();
+
+            if entry.file_name() == "node_modules" {
+                continue;
+            }
+
+            if path.is_dir() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(true)
+    })();
+
+    match result {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(TadaError::Catalog(format!(
+            "{:?} is not empty and is not a scratch app",
+            app_path
+        ))),
+        Err(e) => Err(error(e)),
+    }
+}
+
+fn relative_files(dir: &Path) -> Result<BTreeSet<PathBuf>> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    list_files(dir, &mut files)?;
+
+    Ok(files
+        .iter()
+        .filter_map(|x| x.strip_prefix(dir).ok())
+        .filter(|x| !x.starts_with("node_modules"))
+        .map(Path::to_path_buf)
+        .collect())
+}
+
+/// Generates the app again next to the scratch app, then copies the files
+/// that changed and removes the ones no longer generated. Returns the
+/// updated files and the new manifest.
+fn sync(
+    template: &Details,
+    addons: &[Details],
+    app_path: &Path,
+    generated: &mut BTreeSet<PathBuf>,
+) -> Result<(Vec<PathBuf>, Manifest)> {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("tada-dev-")
+        .tempdir()
+        .map_err(TadaError::fs("Error creating temporary directory"))?;
+    let staging_path = temp_dir
+        .path()
+        .join(app_path.file_name().unwrap_or_default());
+
+    let manifest = generate(
+        template,
+        addons,
+        &staging_path,
+        &ignore_patterns(false),
+        ConflictPolicy::Mode,
+        None,
+    )?;
+    let staged = relative_files(&staging_path)?;
+
+    let mut updated: Vec<PathBuf> = Vec::new();
+
+    for file in &staged {
+        let source = staging_path.join(file);
+        let destination = app_path.join(file);
+
+        let content =
+            fs::read(&source).map_err(TadaError::fs(format!("Error reading {:?}", source)))?;
+        if fs::read(&destination).ok().as_ref() == Some(&content) {
+            continue;
+        }
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).map_err(TadaError::fs(format!(
+                "Error creating directory: {:?}",
+                parent
+            )))?;
+        }
+        fs::write(&destination, content)
+            .map_err(TadaError::fs(format!("Error writing {:?}", destination)))?;
+
+        updated.push(file.clone());
+    }
+
+    for file in generated.difference(&staged) {
+        let _ = fs::remove_file(app_path.join(file));
+        updated.push(file.clone());
+    }
+
+    *generated = staged;
+
+    Ok((updated, manifest))
+}
+
+/// Runs the script in its own process group, so that `stop_script` reaches
+/// the dev server started by the package manager too.
+fn run_script(options: &DevOptions, app_path: &Path) -> Result<Child> {
+    let mut command = pm::command(&options.package_manager)?;
+
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    command
+        .current_dir(app_path)
+        .args(["run", &options.script])
+        .spawn()
+        .map_err(TadaError::fs(format!(
+            "Error running the `{}` script",
+            options.script
+        )))
+}
+
+/// Stops the script and every process it started. Killing the package
+/// manager alone would leave the dev server running, holding its port.
+fn stop_script(child: &mut Child) {
+    #[cfg(unix)]
+    let _ = Command::new("kill")
+        .args(["-s", "TERM", "--", &format!("-{}", child.id())])
+        .status();
+
+    #[cfg(windows)]
+    let _ = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &child.id().to_string()])
+        .status();
+
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn dev(roots: &[PathBuf], options: &DevOptions) -> Result<()> {
+    let mut shadowed: Vec<Shadowed> = Vec::new();
+    let mut templates: BTreeMap<String, Details> = BTreeMap::new();
+    for root in roots {
+        get_templates(root.as_os_str(), &mut templates, &mut shadowed)?;
+    }
+
+    let Some(template) = templates.get(&options.template) else {
+        return Err(TadaError::Catalog(format!(
+            "Template `{}` not found",
+            options.template
+        )));
+    };
+
+    let addons = resolve_addons(roots, template, &options.addons)?;
+
+    let app_path = match &options.dir {
+        Some(dir) => dir.clone(),
+        None => cache_dir()
+            .ok_or_else(|| {
+                TadaError::Catalog("Unable to locate the cache directory, use --dir".to_string())
+            })?
+            .join("dev")
+            .join(&template.name),
+    };
+
+    let generate_spinner = start_spinner("Generating scratch app...");
+    let manifest = clear_scratch_app(&app_path)
+        .and_then(|_| {
+            generate(
+                template,
+                &addons,
+                &app_path,
+                &ignore_patterns(false),
+                ConflictPolicy::Mode,
+                None,
+            )
+        })
+        .inspect_err(|_| generate_spinner.error("Unable to generate the scratch app"))?;
+    let mut generated = relative_files(&app_path)?;
+    fs::write(app_path.join(MARKER), "").map_err(TadaError::fs(format!(
+        "Error writing {:?}",
+        app_path.join(MARKER)
+    )))?;
+    generate_spinner.stop(format!("Scratch app ready at {}", app_path.display()));
+
+    if !manifest.scripts.contains_key(&options.script) {
+        return Err(TadaError::Catalog(format!(
+            "The scratch app has no `{}` script",
+            options.script
+        )));
+    }
+
+    let install_spinner = start_spinner("Installing dependencies...");
+    install_dependencies(&options.package_manager, app_path.clone())
+        .inspect_err(|_| install_spinner.error("Unable to install dependencies"))?;
+    install_spinner.stop("Dependencies installed!");
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(TadaError::fs("Error watching the addons"))?;
+    for addon in &addons {
+        watcher
+            .watch(Path::new(&addon.path), RecursiveMode::Recursive)
+            .map_err(TadaError::fs(format!("Error watching {:?}", addon.path)))?;
+        let _ = log::info(format!("Watching {}", Path::new(&addon.path).display()));
+    }
+
+    // The script runs in its own process group, which does not get Ctrl+C,
+    // so the loop stops it. Ctrl+C is handled as usual once `dev` returns.
+    let interrupt = Interrupt::catch()?;
+
+    let mut child = run_script(options, &app_path)?;
+    let mut dependencies = (manifest.dependencies, manifest.dev_dependencies);
+
+    loop {
+        if interrupt.interrupted() {
+            stop_script(&mut child);
+            return Err(TadaError::Cancelled);
+        }
+
+        let exited = child.try_wait().map_err(TadaError::fs(format!(
+            "Error running the `{}` script",
+            options.script
+        )))?;
+
+        if let Some(status) = exited {
+            if !status.success() {
+                return Err(TadaError::Catalog(format!(
+                    "The `{}` script exited with {}",
+                    options.script, status
+                )));
+            }
+            return Ok(());
+        }
+
+        match receiver.recv_timeout(Duration::from_millis(500)) {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                let _ = log::warning(format!("Error watching the addons: {}", e));
+                continue;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                stop_script(&mut child);
+                return Ok(());
+            }
+        }
+
+        // Editors usually write a file in several steps.
+        while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+
+        let (updated, manifest) = match sync(template, &addons, &app_path, &mut generated) {
+            Ok(synced) => synced,
+            Err(e) => {
+                let _ = log::error(e.to_string());
+                continue;
+            }
+        };
+
+        for file in &updated {
+            let _ = log::step(format!("Updated {}", file.display()));
+        }
+
+        let new_dependencies = (manifest.dependencies, manifest.dev_dependencies);
+        if new_dependencies != dependencies {
+            let _ = log::info("Dependencies changed, installing them again");
+
+            stop_script(&mut child);
+
+            install_dependencies(&options.package_manager, app_path.clone())?;
+
+            child = run_script(options, &app_path)?;
+            dependencies = new_dependencies;
+        }
+    }
+}
+
+/// Generates a scratch app with the addons under development, runs its dev
+/// script and applies every change made to the addons to the app.
+pub fn dev_addons(roots: &[PathBuf], options: &DevOptions) -> Result<()> {
+    dev(roots, options)?;
+
+    let _ = outro("Done!");
+
+    Ok(())
+}
@@ -0,0 +1,5 @@
+pub mod dev;
+pub mod snapshot;
+pub mod test;
+pub mod upgrade;
+pub mod validate;
@@ -0,0 +1,443 @@
+#![deny(clippy::all)]
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use cliclack::{log, outro};
+use diffy::create_patch;
+
+use crate::error::{Result, TadaError};
+use crate::generate::{generate, ignore_patterns, Manifest};
+use crate::utils::conflict::{ConflictPolicy, Resolution};
+use crate::utils::diagnostic::{parse_json_file, JsonDiagnostic};
+use crate::utils::fs::{
+    get_filtered_addons, get_scenarios, get_templates, list_files, Details, ScenarioJson, Shadowed,
+};
+use crate::utils::registry::{Registry, RegistryReport};
+
+/// Sorted list of the generated files, one per line.
+const TREE_FILE: &str = "tree.txt";
+/// Copy of the generated text files.
+const FILES_DIR: &str = "files";
+
+/// Contents of a snapshot directory, keyed by `/`-separated relative path.
+type Snapshot = BTreeMap<String, Vec<u8>>;
+
+#[derive(Debug)]
+pub struct SnapshotOptions {
+    /// Only these scenarios, all of them when empty.
+    pub scenarios: Vec<String>,
+    pub update: bool,
+    /// Registry and dist-tag of the `latest` scenarios.
+    pub registry: String,
+    pub registry_timeout: Duration,
+    pub dist_tag: String,
+}
+
+fn relative_key(path: &Path, base: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .components()
+        .map(|x| x.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Snapshot of a generated app: a tree listing and the text files with
+/// their line endings normalized. Binary files are only listed.
+fn take_snapshot(app_path: &Path) -> Result<Snapshot> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    list_files(app_path, &mut files)?;
+
+    let mut snapshot = Snapshot::new();
+    let mut tree: BTreeMap<String, String> = BTreeMap::new();
+
+    for file in files {
+        let key = relative_key(&file, app_path);
+        let bytes = fs::read(&file).map_err(TadaError::fs(format!("Error reading {:?}", file)))?;
+
+        match String::from_utf8(bytes) {
+            Ok(text) => {
+                tree.insert(key.clone(), key.clone());
+                snapshot.insert(
+                    format!("{}/{}", FILES_DIR, key),
+                    text.replace("\r\n", "\n").into_bytes(),
+                );
+            }
+            Err(e) => {
+                let line = format!("{} (binary, {} bytes)", key, e.as_bytes().len());
+                tree.insert(key, line);
+            }
+        }
+    }
+
+    let mut listing = tree.into_values().collect::<Vec<_>>().join("\n");
+    listing.push('\n');
+    snapshot.insert(TREE_FILE.to_string(), listing.into_bytes());
+
+    Ok(snapshot)
+}
+
+fn read_snapshot(dir: &Path) -> Result<Snapshot> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    list_files(dir, &mut files)?;
+
+    let mut snapshot = Snapshot::new();
+
+    for file in files {
+        let bytes = fs::read(&file).map_err(TadaError::fs(format!("Error reading {:?}", file)))?;
+        snapshot.insert(relative_key(&file, dir), bytes);
+    }
+
+    Ok(snapshot)
+}
+
+fn write_snapshot(dir: &Path, snapshot: &Snapshot) -> Result<()> {
+    if dir.exists() {
+        fs::remove_dir_all(dir).map_err(TadaError::fs(format!("Error removing {:?}", dir)))?;
+    }
+
+    for (key, bytes) in snapshot {
+        let path = dir.join(key);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(TadaError::fs(format!(
+                "Error creating directory: {:?}",
+                parent
+            )))?;
+        }
+
+        fs::write(&path, bytes).map_err(TadaError::fs(format!("Error writing {:?}", path)))?;
+    }
+
+    Ok(())
+}
+
+/// One message per difference, with a unified diff for changed files.
+fn diff_snapshots(expected: &Snapshot, actual: &Snapshot) -> Vec<String> {
+    let mut differences: Vec<String> = Vec::new();
+
+    for (key, expected_bytes) in expected {
+        match actual.get(key) {
+            None => differences.push(format!("{}: removed", key)),
+            Some(actual_bytes) if actual_bytes != expected_bytes => {
+                let expected_text = String::from_utf8_lossy(expected_bytes);
+                let actual_text = String::from_utf8_lossy(actual_bytes);
+                let patch = create_patch(&expected_text, &actual_text);
+                differences.push(format!("{}: changed\n{}", key, patch));
+            }
+            Some(_) => {}
+        }
+    }
+
+    for key in actual.keys() {
+        if !expected.contains_key(key) {
+            differences.push(format!("{}: added", key));
+        }
+    }
+
+    differences
+}
+
+/// Scenarios run without prompts, `ask` is refused before generating.
+fn no_prompt(path: &Path, _diff: Option<&str>) -> Result<Resolution> {
+    Err(TadaError::Catalog(format!(
+        "Unable to ask about {} in a scenario",
+        path.display()
+    )))
+}
+
+fn scenario_policy(scenario: &ScenarioJson) -> Result<ConflictPolicy> {
+    let policy = scenario.onConflict.as_deref().unwrap_or("mode");
+
+    match policy {
+        "ask" => None,
+        policy => ConflictPolicy::parse(policy, no_prompt),
+    }
+    .ok_or_else(|| {
+        TadaError::Catalog(format!(
+            "invalid `onConflict` `{}`, expected one of: mode, keep, take, markers",
+            policy
+        ))
+    })
+}
+
+/// Resolves the dependencies of the generated app against the registry.
+/// Fails when the registry cannot be reached, the snapshot would not tell.
+fn resolve_latest(app_path: &Path, options: &SnapshotOptions) -> Result<()> {
+    let registry = Registry::new(&options.registry, options.registry_timeout);
+    let mut report = RegistryReport::default();
+
+    let mut manifest = Manifest::read(app_path)?;
+    for dependencies in [&mut manifest.dependencies, &mut manifest.dev_dependencies] {
+        registry.resolve(dependencies, &options.dist_tag, false, &mut report);
+    }
+
+    if report.offline {
+        return Err(TadaError::Catalog(format!(
+            "Unable to reach {}",
+            options.registry
+        )));
+    }
+
+    let name = manifest.package_json.name.clone();
+    manifest.write(app_path, name)
+}
+
+/// Generates the scenario into a temporary directory and snapshots it.
+fn generate_scenario(
+    scenario: &ScenarioJson,
+    templates: &BTreeMap<String, Details>,
+    roots: &[PathBuf],
+    options: &SnapshotOptions,
+) -> Result<Snapshot> {
+    let Some(template) = templates.get(&scenario.template) else {
+        return Err(TadaError::Catalog(format!(
+            "template `{}` not found",
+            scenario.template
+        )));
+    };
+
+    let mut shadowed: Vec<Shadowed> = Vec::new();
+    let mut invalid_addons: Vec<JsonDiagnostic> = Vec::new();
+    let mut compatible_addons: BTreeMap<String, Details> = BTreeMap::new();
+    for root in roots {
+        get_filtered_addons(
+            root.as_os_str(),
+            template.name.clone(),
+            &mut compatible_addons,
+            &mut shadowed,
+            &mut invalid_addons,
+        )?;
+    }
+
+    let mut addons: Vec<Details> = Vec::new();
+    for name in &scenario.addons {
+        match compatible_addons.get(name) {
+            Some(addon) => addons.push(addon.clone()),
+            None => {
+                return Err(TadaError::Catalog(format!(
+                    "addon `{}` not found or not compatible with template `{}`",
+                    name, template.name
+                )))
+            }
+        }
+    }
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("tada-snapshot-")
+        .tempdir()
+        .map_err(TadaError::fs("Error creating temporary directory"))?;
+    let app_path = temp_dir.path().join("app");
+
+    generate(
+        template,
+        &addons,
+        &app_path,
+        &ignore_patterns(scenario.skipLockfiles),
+        scenario_policy(scenario)?,
+        scenario.packageName.as_deref(),
+    )?;
+
+    if scenario.latest {
+        resolve_latest(&app_path, options)?;
+    }
+
+    take_snapshot(&app_path)
+}
+
+/// Checks every scenario of the catalogs against its snapshot, or rewrites
+/// the snapshots when `update` is set. Fails when a scenario fails or
+/// differs.
+pub fn snapshot_scenarios(roots: &[PathBuf], options: &SnapshotOptions) -> Result<()> {
+    let only = &options.scenarios;
+    let update = options.update;
+
+    let mut shadowed: Vec<Shadowed> = Vec::new();
+
+    let mut templates: BTreeMap<String, Details> = BTreeMap::new();
+    let mut scenarios: BTreeMap<String, Details> = BTreeMap::new();
+    for root in roots {
+        get_templates(root.as_os_str(), &mut templates, &mut shadowed)?;
+        get_scenarios(root.as_os_str(), &mut scenarios, &mut shadowed)?;
+    }
+
+    for name in only {
+        if !scenarios.contains_key(name) {
+            return Err(TadaError::Catalog(format!("Scenario `{}` not found", name)));
+        }
+    }
+
+    if scenarios.is_empty() {
+        return Err(TadaError::Catalog(
+            "No scenario found in the `snapshots` directories".to_string(),
+        ));
+    }
+
+    let mut failures = 0;
+    let mut checked = 0;
+
+    for (name, details) in &scenarios {
+        if !only.is_empty() && !only.contains(name) {
+            continue;
+        }
+        checked += 1;
+
+        let snapshot_dir = Path::new(&details.path).with_extension("");
+
+        let result = parse_json_file::<ScenarioJson>(&details.path)
+            .map_err(TadaError::from)
+            .and_then(|scenario| generate_scenario(&scenario, &templates, roots, options));
+
+        let actual = match result {
+            Ok(actual) => actual,
+            Err(e) => {
+                failures += 1;
+                let _ = log::error(format!("{}: {}", name, e));
+                continue;
+            }
+        };
+
+        if update {
+            match write_snapshot(&snapshot_dir, &actual) {
+                Ok(_) => {
+                    let _ = log::step(format!("Updated {}", snapshot_dir.display()));
+                }
+                Err(e) => {
+                    failures += 1;
+                    let _ = log::error(format!("{}: {}", name, e));
+                }
+            }
+            continue;
+        }
+
+        if !snapshot_dir.is_dir() {
+            failures += 1;
+            let _ = log::error(format!(
+                "{}: no snapshot at {}, run with --update to create it",
+                name,
+                snapshot_dir.display()
+            ));
+            continue;
+        }
+
+        let expected = match read_snapshot(&snapshot_dir) {
+            Ok(expected) => expected,
+            Err(e) => {
+                failures += 1;
+                let _ = log::error(format!("{}: {}", name, e));
+                continue;
+            }
+        };
+
+        let differences = diff_snapshots(&expected, &actual);
+
+        if differences.is_empty() {
+            let _ = log::success(format!("{}: matches", name));
+        } else {
+            failures += 1;
+            let _ = log::error(format!(
+                "{}: differs from its snapshot\n{}",
+                name,
+                differences.join("\n")
+            ));
+        }
+    }
+
+    if failures == 0 {
+        let message = match update {
+            true => format!("{} snapshot(s) updated!", checked),
+            false => format!("{} snapshot(s) match!", checked),
+        };
+        let _ = outro(message);
+        return Ok(());
+    }
+
+    Err(TadaError::Catalog(format!(
+        "{} of {} scenario(s) failed, run with --update to accept the changes",
+        failures, checked
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(json: &str) -> ScenarioJson {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn options() -> SnapshotOptions {
+        SnapshotOptions {
+            scenarios: Vec::new(),
+            update: false,
+            registry: "http://127.0.0.1:9".to_string(),
+            registry_timeout: Duration::from_secs(1),
+            dist_tag: "latest".to_string(),
+        }
+    }
+
+    #[test]
+    fn scenarios_run_without_prompts() {
+        let policy = scenario_policy(&scenario(r#"{"template": "react", "onConflict": "take"}"#));
+        assert!(matches!(policy, Ok(ConflictPolicy::Take)));
+
+        for policy in ["ask", "nope"] {
+            let json = format!(r#"{{"template": "react", "onConflict": "{}"}}"#, policy);
+            assert_eq!(
+                scenario_policy(&scenario(&json)).unwrap_err().code(),
+                "ERR_TADA_CATALOG"
+            );
+        }
+    }
+
+    #[test]
+    fn scenario_options_are_applied() {
+        let catalog = tempfile::tempdir().unwrap();
+        let template_path = catalog.path().join("templates/react");
+        fs::create_dir_all(&template_path).unwrap();
+        fs::write(template_path.join("package.json"), r#"{"name": "react"}"#).unwrap();
+        fs::write(template_path.join("pnpm-lock.yaml"), "").unwrap();
+
+        let templates = BTreeMap::from([(
+            "react".to_string(),
+            Details {
+                name: "react".to_string(),
+                path: template_path.into_os_string(),
+                root: catalog.path().as_os_str().to_os_string(),
+            },
+        )]);
+        let roots = [catalog.path().to_path_buf()];
+
+        let snapshot = generate_scenario(
+            &scenario(
+                r#"{"template": "react", "packageName": "@tada/app", "skipLockfiles": true}"#,
+            ),
+            &templates,
+            &roots,
+            &options(),
+        )
+        .unwrap();
+
+        assert_eq!(snapshot[TREE_FILE], b"package.json\n");
+        let package_json = String::from_utf8(snapshot["files/package.json"].clone()).unwrap();
+        assert!(package_json.contains(r#""name": "@tada/app""#));
+
+        // The snapshot would not tell an offline registry from a release.
+        fs::write(
+            Path::new(&templates["react"].path).join("package.json"),
+            r#"{"name": "react", "dependencies": {"react": "^19.0.0"}}"#,
+        )
+        .unwrap();
+        let error = generate_scenario(
+            &scenario(r#"{"template": "react", "latest": true}"#),
+            &templates,
+            &roots,
+            &options(),
+        )
+        .unwrap_err();
+        assert!(error.to_string().starts_with("Unable to reach"));
+    }
+}
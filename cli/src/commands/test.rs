@@ -0,0 +1,351 @@
+#![deny(clippy::all)]
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use cliclack::{log, note, outro};
+
+use crate::error::{Result, TadaError};
+use crate::generate::{generate, ignore_patterns};
+use crate::start_spinner;
+use crate::utils::conflict::ConflictPolicy;
+use crate::utils::diagnostic::JsonDiagnostic;
+use crate::utils::fs::{get_filtered_addons, get_templates, Details, Shadowed};
+use crate::utils::pm;
+
+/// Lines of output shown for a failed step.
+const OUTPUT_TAIL: usize = 20;
+
+#[derive(Debug)]
+pub struct TestOptions {
+    pub templates: Vec<String>,
+    pub addons: Vec<String>,
+    pub max_addons: usize,
+    pub install: bool,
+    pub scripts: Vec<String>,
+    pub keep: bool,
+    pub package_manager: String,
+}
+
+#[derive(Debug)]
+enum Outcome {
+    Pass,
+    Fail,
+    /// The step did not run, e.g. a script the app does not declare.
+    Skip,
+}
+
+impl Outcome {
+    fn label(&self) -> &'static str {
+        match self {
+            Outcome::Pass => "pass",
+            Outcome::Fail => "FAIL",
+            Outcome::Skip => "skip",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Case {
+    template: Details,
+    addons: Vec<Details>,
+}
+
+impl Case {
+    fn label(&self) -> String {
+        std::iter::once(self.template.name.as_str())
+            .chain(self.addons.iter().map(|x| x.name.as_str()))
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
+}
+
+/// Every combination of `1..=max` items of `items`, in order.
+fn combinations<T: Clone>(items: &[T], max: usize) -> Vec<Vec<T>> {
+    fn collect<T: Clone>(
+        items: &[T],
+        size: usize,
+        current: &mut Vec<T>,
+        combinations: &mut Vec<Vec<T>>,
+    ) {
+        if current.len() == size {
+            combinations.push(current.clone());
+            return;
+        }
+
+        for (index, item) in items.iter().enumerate() {
+            current.push(item.clone());
+            collect(&items[index + 1..], size, current, combinations);
+            current.pop();
+        }
+    }
+
+    let mut combinations: Vec<Vec<T>> = Vec::new();
+
+    for size in 1..=max.min(items.len()) {
+        collect(items, size, &mut Vec::new(), &mut combinations);
+    }
+
+    combinations
+}
+
+fn collect_cases(roots: &[PathBuf], options: &TestOptions) -> Result<Vec<Case>> {
+    let mut shadowed: Vec<Shadowed> = Vec::new();
+
+    let mut templates: BTreeMap<String, Details> = BTreeMap::new();
+    for root in roots {
+        get_templates(root.as_os_str(), &mut templates, &mut shadowed)?;
+    }
+
+    for name in &options.templates {
+        if !templates.contains_key(name) {
+            return Err(TadaError::Catalog(format!("Template `{}` not found", name)));
+        }
+    }
+
+    let mut cases: Vec<Case> = Vec::new();
+
+    for template in templates.values() {
+        if !options.templates.is_empty() && !options.templates.contains(&template.name) {
+            continue;
+        }
+
+        let mut addons: BTreeMap<String, Details> = BTreeMap::new();
+        let mut invalid_addons: Vec<JsonDiagnostic> = Vec::new();
+        for root in roots {
+            get_filtered_addons(
+                root.as_os_str(),
+                template.name.clone(),
+                &mut addons,
+                &mut shadowed,
+                &mut invalid_addons,
+            )?;
+        }
+
+        for diagnostic in &invalid_addons {
+            let _ = log::warning(format!("Skipping addon, invalid {}", diagnostic));
+        }
+
+        if options.addons.is_empty() {
+            cases.push(Case {
+                template: template.clone(),
+                addons: Vec::new(),
+            });
+        }
+
+        let addons = addons.into_values().collect::<Vec<_>>();
+
+        for combination in combinations(&addons, options.max_addons) {
+            if !options.addons.is_empty()
+                && !combination.iter().any(|x| options.addons.contains(&x.name))
+            {
+                continue;
+            }
+
+            cases.push(Case {
+                template: template.clone(),
+                addons: combination,
+            });
+        }
+    }
+
+    Ok(cases)
+}
+
+/// Runs `command` and returns the tail of its output when it fails.
+fn run(command: &mut Command) -> std::result::Result<(), String> {
+    let output = command
+        .output()
+        .map_err(|e| format!("Error running {:?}: {}", command, e))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let output = [output.stdout, output.stderr].concat();
+    let output = String::from_utf8_lossy(&output);
+    let lines = output.lines().collect::<Vec<_>>();
+
+    Err(lines[lines.len().saturating_sub(OUTPUT_TAIL)..].join("\n"))
+}
+
+/// Generates, installs and runs the scripts of one case. Returns one outcome
+/// per step, along with the failure output.
+fn run_case(
+    case: &Case,
+    app_path: &Path,
+    steps: &[String],
+    options: &TestOptions,
+) -> (Vec<Outcome>, Option<(String, String)>) {
+    let mut outcomes: Vec<Outcome> = Vec::new();
+
+    let manifest = match generate(
+        &case.template,
+        &case.addons,
+        app_path,
+        &ignore_patterns(false),
+        ConflictPolicy::Mode,
+        None,
+    ) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            outcomes.push(Outcome::Fail);
+            return (outcomes, Some((steps[0].clone(), e.to_string())));
+        }
+    };
+    outcomes.push(Outcome::Pass);
+
+    if !options.install {
+        return (outcomes, None);
+    }
+
+    let result = pm::command(&options.package_manager)
+        .map_err(|e| e.to_string())
+        .and_then(|mut command| run(command.current_dir(app_path).arg("install")));
+    if let Err(output) = result {
+        outcomes.push(Outcome::Fail);
+        return (outcomes, Some((steps[1].clone(), output)));
+    }
+    outcomes.push(Outcome::Pass);
+
+    for script in &steps[2..] {
+        if !manifest.scripts.contains_key(script) {
+            outcomes.push(Outcome::Skip);
+            continue;
+        }
+
+        let result = pm::command(&options.package_manager)
+            .map_err(|e| e.to_string())
+            .and_then(|mut command| run(command.current_dir(app_path).args(["run", script])));
+        if let Err(output) = result {
+            outcomes.push(Outcome::Fail);
+            return (outcomes, Some((script.clone(), output)));
+        }
+        outcomes.push(Outcome::Pass);
+    }
+
+    (outcomes, None)
+}
+
+fn format_matrix(steps: &[String], rows: &[(String, Vec<Outcome>)]) -> String {
+    let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let widths = steps.iter().map(|x| x.len().max(4)).collect::<Vec<_>>();
+
+    let mut lines: Vec<String> = Vec::new();
+
+    let mut header = format!("{:label_width$}", "");
+    for (step, width) in steps.iter().zip(&widths) {
+        header.push_str(&format!("  {:width$}", step));
+    }
+    lines.push(header.trim_end().to_string());
+
+    for (label, outcomes) in rows {
+        let mut line = format!("{:label_width$}", label);
+
+        for (index, width) in widths.iter().enumerate() {
+            let cell = outcomes.get(index).map(|x| x.label()).unwrap_or("-");
+            line.push_str(&format!("  {:width$}", cell));
+        }
+
+        lines.push(line.trim_end().to_string());
+    }
+
+    lines.join("\n")
+}
+
+/// Generates every compatible template and addon combination of the
+/// catalogs into a temporary directory, then installs and runs the scripts
+/// when asked. Fails when any combination fails.
+pub fn test_catalogs(roots: &[PathBuf], options: &TestOptions) -> Result<()> {
+    let cases = collect_cases(roots, options)?;
+
+    if cases.is_empty() {
+        return Err(TadaError::Catalog("No combination to test".to_string()));
+    }
+
+    let mut steps = vec!["generate".to_string()];
+    if options.install {
+        steps.push("install".to_string());
+
+        if options.scripts.is_empty() {
+            steps.push("build".to_string());
+        } else {
+            steps.extend(options.scripts.iter().cloned());
+        }
+    }
+
+    let mut rows: Vec<(String, Vec<Outcome>)> = Vec::new();
+    let mut failures = 0;
+
+    for case in &cases {
+        let label = case.label();
+        let spinner = start_spinner(&format!("Testing {}...", label));
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("tada-test-")
+            .tempdir()
+            .map_err(TadaError::fs("Error creating temporary directory"))
+            .inspect_err(|_| spinner.error("Unable to test"))?;
+        let app_path = temp_dir.path().join("app");
+
+        let (outcomes, failure) = run_case(case, &app_path, &steps, options);
+
+        match &failure {
+            Some((step, output)) => {
+                failures += 1;
+                spinner.error(format!("{}: {} failed", label, step));
+                let _ = log::error(output);
+            }
+            None => spinner.stop(format!("{}: passed", label)),
+        }
+
+        if options.keep {
+            let kept_path = temp_dir.keep();
+            let _ = log::remark(format!("Kept {}", kept_path.join("app").display()));
+        }
+
+        rows.push((label, outcomes));
+    }
+
+    let _ = note("Results", format_matrix(&steps, &rows));
+
+    if failures == 0 {
+        let _ = outro(format!("{} combination(s) passed!", rows.len()));
+        return Ok(());
+    }
+
+    Err(TadaError::Catalog(format!(
+        "{} of {} combination(s) failed",
+        failures,
+        rows.len()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combinations_up_to_max() {
+        assert_eq!(
+            combinations(&["a", "b", "c"], 2),
+            vec![
+                vec!["a"],
+                vec!["b"],
+                vec!["c"],
+                vec!["a", "b"],
+                vec!["a", "c"],
+                vec!["b", "c"],
+            ]
+        );
+    }
+
+    #[test]
+    fn combinations_are_capped_by_the_items() {
+        assert_eq!(combinations(&["a", "b"], 5).len(), 3);
+        assert_eq!(combinations(&["a", "b", "c", "d"], 4).len(), 15);
+        assert!(combinations(&["a"], 0).is_empty());
+        assert!(combinations::<&str>(&[], 3).is_empty());
+    }
+}
@@ -0,0 +1,685 @@
+#![deny(clippy::all)]
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use cliclack::{log, note, outro};
+use diffy::create_patch;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, TadaError};
+use crate::generate::{apply_addon, copy_template, ignore_patterns, Manifest};
+use crate::start_spinner;
+use crate::utils::conflict::{open_in_editor, ConflictPolicy, Resolution};
+use crate::utils::diagnostic::{parse_json_file, JsonDiagnostic};
+use crate::utils::fs::{get_filtered_addons, get_templates, list_files, Details, Shadowed};
+
+/// Where an app keeps what it was generated from, relative to its root.
+pub const GENERATION_DIR: &str = ".tada";
+const GENERATION_JSON: &str = "manifest.json";
+/// Archive of the files of the last generation, the base of the next
+/// upgrade. Compressed, so that it stays small and out of the way of the
+/// tools of the app.
+const BASE_ARCHIVE: &str = "base.tar.gz";
+/// Not part of the generated files, at the root of the app.
+const NOT_GENERATED: [&str; 3] = [GENERATION_DIR, ".git", "node_modules"];
+
+/// A dependency `--latest` resolved, replayed by `upgrade` as long as the
+/// catalog still declares the same range.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LatestVersion {
+    pub declared: String,
+    pub resolved: String,
+}
+
+/// What an app was generated from.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[allow(non_snake_case)]
+pub struct GenerationJson {
+    pub template: String,
+    pub addons: Vec<String>,
+    pub packageName: String,
+    /// Set when the app was generated into a workspace, whose lockfile is
+    /// used instead of the template one.
+    #[serde(default)]
+    pub skipLockfiles: bool,
+    /// Catalog roots, as given, local paths made absolute. They are looked
+    /// up before the configured ones on upgrade.
+    pub roots: Vec<String>,
+    #[serde(default)]
+    pub latestVersions: BTreeMap<String, LatestVersion>,
+}
+
+#[derive(Debug)]
+pub struct UpgradeOptions {
+    pub dir: PathBuf,
+    pub dry_run: bool,
+    /// Settles the files both the app and the catalog changed on the same
+    /// lines. `Mode` and `Markers` leave conflict markers.
+    pub policy: ConflictPolicy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Added,
+    Updated,
+    Removed,
+    /// Both the app and the catalog changed the file, without overlap.
+    Merged,
+    /// Both changed the same lines, the file has conflict markers.
+    Conflict,
+    /// The catalog changed a file the app removed, or removed a file the
+    /// app changed: the app version is kept.
+    Kept,
+}
+
+impl Change {
+    fn label(&self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Updated => "updated",
+            Change::Removed => "removed",
+            Change::Merged => "merged",
+            Change::Conflict => "CONFLICT",
+            Change::Kept => "kept",
+        }
+    }
+}
+
+/// The generated files of the app at `dir`, by path relative to it.
+fn generated_files(dir: &Path) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    if dir.is_dir() {
+        list_files(dir, &mut files)?;
+    }
+
+    let mut contents: BTreeMap<PathBuf, Vec<u8>> = BTreeMap::new();
+    for file in files {
+        let Ok(relative) = file.strip_prefix(dir) else {
+            continue;
+        };
+
+        if NOT_GENERATED.iter().any(|x| relative.starts_with(x)) {
+            continue;
+        }
+
+        let bytes = fs::read(&file).map_err(TadaError::fs(format!("Error reading {:?}", file)))?;
+        contents.insert(relative.to_path_buf(), bytes);
+    }
+
+    Ok(contents)
+}
+
+fn write_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(TadaError::fs(format!(
+            "Error creating directory: {:?}",
+            parent
+        )))?;
+    }
+
+    fs::write(path, bytes).map_err(TadaError::fs(format!("Error writing {:?}", path)))
+}
+
+/// Writes `files` to a `.tar.gz` archive. Entries are sorted and carry no
+/// timestamp, so the same files always give the same archive.
+fn write_archive(path: &Path, files: &BTreeMap<PathBuf, Vec<u8>>) -> Result<()> {
+    let error = TadaError::fs(format!("Error writing {:?}", path));
+
+    let result = (|| -> std::io::Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+
+        for (file, bytes) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(0);
+            builder.append_data(&mut header, file, bytes.as_slice())?;
+        }
+
+        builder.into_inner()?.finish()
+    })();
+
+    match result {
+        Ok(archive) => write_file(path, &archive),
+        Err(e) => Err(error(e)),
+    }
+}
+
+fn read_archive(path: &Path) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let error = TadaError::fs(format!("Error reading {:?}", path));
+
+    let result = (|| -> std::io::Result<BTreeMap<PathBuf, Vec<u8>>> {
+        let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+        let mut files: BTreeMap<PathBuf, Vec<u8>> = BTreeMap::new();
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+            files.insert(entry.path()?.to_path_buf(), bytes);
+        }
+
+        Ok(files)
+    })();
+
+    result.map_err(error)
+}
+
+/// Generates the selection into a temporary directory and returns its
+/// files.
+fn generate_output(
+    template: &Details,
+    addons: &[Details],
+    generation: &GenerationJson,
+    policy: ConflictPolicy,
+) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("tada-upgrade-")
+        .tempdir()
+        .map_err(TadaError::fs("Error creating temporary directory"))?;
+    let app_path = temp_dir.path().join("app");
+
+    fs::create_dir_all(&app_path).map_err(TadaError::fs(format!(
+        "Error creating directory: {:?}",
+        app_path
+    )))?;
+
+    copy_template(
+        template,
+        &app_path,
+        &ignore_patterns(generation.skipLockfiles),
+    )?;
+
+    let mut manifest = Manifest::read(&app_path)?;
+
+    for addon in addons {
+        apply_addon(addon, &app_path, &mut manifest, policy)?;
+    }
+
+    manifest.dedupe();
+
+    for dependencies in [&mut manifest.dependencies, &mut manifest.dev_dependencies] {
+        for (name, version) in dependencies.iter_mut() {
+            match generation.latestVersions.get(name) {
+                Some(latest) if latest.declared == *version => {
+                    version.clone_from(&latest.resolved);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    manifest.write(&app_path, generation.packageName.clone())?;
+
+    generated_files(&app_path)
+}
+
+/// Generates the selection for `upgrade`, the base when the app is
+/// generated and the new version on upgrade. Both follow the entry modes,
+/// without asking, whatever `--on-conflict` the app was generated with:
+/// the conflicts settled then are changes of the app, merged like the
+/// others. When the modes alone cannot apply an addon, the existing files
+/// are kept instead.
+fn regenerate(
+    template: &Details,
+    addons: &[Details],
+    generation: &GenerationJson,
+) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+    match generate_output(template, addons, generation, ConflictPolicy::Mode) {
+        Err(e) if e.code() == "ERR_TADA_MERGE" => {
+            generate_output(template, addons, generation, ConflictPolicy::Keep)
+        }
+        result => result,
+    }
+}
+
+/// Records what the app at `app_path` was generated from, along with the
+/// files of the selection, for `upgrade` to tell the changes made to the
+/// app from the ones made to the catalog.
+pub fn record_generation(
+    app_path: &Path,
+    template: &Details,
+    addons: &[Details],
+    generation: &GenerationJson,
+) -> Result<()> {
+    let generation_path = app_path.join(GENERATION_DIR);
+
+    let files = regenerate(template, addons, generation)?;
+    write_archive(&generation_path.join(BASE_ARCHIVE), &files)?;
+
+    let generation_json = serde_json::to_string_pretty(generation)
+        .map_err(TadaError::fs("Error serializing the generation manifest"))?;
+    write_file(
+        &generation_path.join(GENERATION_JSON),
+        generation_json.as_bytes(),
+    )
+}
+
+/// Reads the generation manifest of the app at `dir`.
+pub fn read_generation(dir: &Path) -> Result<GenerationJson> {
+    let generation_json_path = dir.join(GENERATION_DIR).join(GENERATION_JSON);
+
+    if !generation_json_path.is_file() {
+        return Err(TadaError::Upgrade(format!(
+            "{} not found, only apps generated with a generation manifest can be upgraded",
+            generation_json_path.display()
+        )));
+    }
+
+    Ok(parse_json_file(generation_json_path.as_os_str())?)
+}
+
+/// Finds the recorded template and addons in the current catalogs.
+fn resolve_selection(
+    roots: &[PathBuf],
+    generation: &GenerationJson,
+) -> Result<(Details, Vec<Details>)> {
+    let mut shadowed: Vec<Shadowed> = Vec::new();
+
+    let mut templates: BTreeMap<String, Details> = BTreeMap::new();
+    for root in roots {
+        get_templates(root.as_os_str(), &mut templates, &mut shadowed)?;
+    }
+
+    let Some(template) = templates.remove(&generation.template) else {
+        return Err(TadaError::Catalog(format!(
+            "Template `{}` not found",
+            generation.template
+        )));
+    };
+
+    let mut invalid_addons: Vec<JsonDiagnostic> = Vec::new();
+    let mut compatible_addons: BTreeMap<String, Details> = BTreeMap::new();
+    for root in roots {
+        get_filtered_addons(
+            root.as_os_str(),
+            template.name.clone(),
+            &mut compatible_addons,
+            &mut shadowed,
+            &mut invalid_addons,
+        )?;
+    }
+
+    let mut addons: Vec<Details> = Vec::new();
+    for name in &generation.addons {
+        match compatible_addons.get(name) {
+            Some(addon) => addons.push(addon.clone()),
+            None => {
+                return Err(TadaError::Catalog(format!(
+                    "addon `{}` not found or not compatible with template `{}`",
+                    name, template.name
+                )))
+            }
+        }
+    }
+
+    Ok((template, addons))
+}
+
+fn as_text(bytes: &[u8]) -> Option<&str> {
+    match content_inspector::inspect(bytes).is_binary() {
+        true => None,
+        false => std::str::from_utf8(bytes).ok(),
+    }
+}
+
+/// Three-way merge of one file: `base` is the last generated version,
+/// `ours` the one in the app and `theirs` the newly generated one. Returns
+/// the change and the new content of the app file, `None` to remove it.
+fn merge_file(
+    base: Option<&Vec<u8>>,
+    ours: Option<&Vec<u8>>,
+    theirs: Option<&Vec<u8>>,
+) -> Option<(Change, Option<Vec<u8>>)> {
+    // Unchanged in the catalog, or already up to date in the app.
+    if base == theirs || ours == theirs {
+        return None;
+    }
+
+    // Untouched in the app.
+    if ours == base {
+        let change = match (ours, theirs) {
+            (None, _) => Change::Added,
+            (_, None) => Change::Removed,
+            _ => Change::Updated,
+        };
+        return Some((change, theirs.cloned()));
+    }
+
+    let (Some(ours), Some(theirs)) = (ours, theirs) else {
+        return Some((Change::Kept, ours.cloned()));
+    };
+
+    let base = base.map(|x| as_text(x)).unwrap_or(Some(""));
+
+    match (base, as_text(ours), as_text(theirs)) {
+        (Some(base), Some(ours), Some(theirs)) => match diffy::merge(base, ours, theirs) {
+            Ok(merged) => Some((Change::Merged, Some(merged.into_bytes()))),
+            Err(conflicted) => Some((Change::Conflict, Some(conflicted.into_bytes()))),
+        },
+        // Binary files cannot hold markers.
+        _ => Some((Change::Conflict, Some(ours.clone()))),
+    }
+}
+
+/// Settles the conflict on `path`, changed on both sides, with `policy`.
+/// `conflicted` is the content with markers, or the app version of binary
+/// files. Returns the change and the new content of the app file, and
+/// whether to open it in the editor once written.
+fn settle_conflict(
+    path: &Path,
+    ours: &[u8],
+    theirs: &[u8],
+    conflicted: Vec<u8>,
+    policy: ConflictPolicy,
+) -> Result<(Change, Vec<u8>, bool)> {
+    let resolution = match policy {
+        ConflictPolicy::Mode | ConflictPolicy::Markers => Resolution::Markers,
+        ConflictPolicy::Keep => Resolution::Keep,
+        ConflictPolicy::Take => Resolution::Take,
+        ConflictPolicy::Ask(ask) => {
+            let diff = match (as_text(ours), as_text(theirs)) {
+                (Some(ours), Some(theirs)) => Some(create_patch(ours, theirs).to_string()),
+                _ => None,
+            };
+            ask(path, diff.as_deref())?
+        }
+    };
+
+    Ok(match resolution {
+        Resolution::Keep => (Change::Kept, ours.to_vec(), false),
+        Resolution::Take => (Change::Updated, theirs.to_vec(), false),
+        Resolution::Markers => (Change::Conflict, conflicted, false),
+        Resolution::Edit => (Change::Merged, conflicted, true),
+    })
+}
+
+/// Regenerates the app from the current catalogs and merges the changes
+/// made to the catalogs since the last generation into it, keeping the
+/// changes made to the app.
+pub fn upgrade_app(roots: &[PathBuf], options: &UpgradeOptions) -> Result<()> {
+    let base_path = options.dir.join(GENERATION_DIR).join(BASE_ARCHIVE);
+
+    let generation = read_generation(&options.dir)?;
+    if !base_path.is_file() {
+        return Err(TadaError::Upgrade(format!(
+            "{} not found, the files of the last generation are needed to upgrade",
+            base_path.display()
+        )));
+    }
+    let base = read_archive(&base_path)?;
+    let (template, addons) = resolve_selection(roots, &generation)?;
+
+    let spinner = start_spinner("Generating the new version...");
+    let theirs = regenerate(&template, &addons, &generation)
+        .inspect_err(|_| spinner.error("Unable to generate the new version"))?;
+    spinner.stop("New version ready!");
+
+    let paths = base.keys().chain(theirs.keys()).collect::<BTreeSet<_>>();
+
+    let mut changes: Vec<(PathBuf, Change)> = Vec::new();
+
+    for path in paths {
+        let app_file = options.dir.join(path);
+        let ours = fs::read(&app_file).ok();
+
+        let Some((change, content)) = merge_file(base.get(path), ours.as_ref(), theirs.get(path))
+        else {
+            continue;
+        };
+
+        let (change, content, edit) = match (change, &ours, theirs.get(path), content) {
+            (Change::Conflict, Some(ours), Some(theirs), Some(conflicted)) if !options.dry_run => {
+                let (change, content, edit) =
+                    settle_conflict(path, ours, theirs, conflicted, options.policy)?;
+                (change, Some(content), edit)
+            }
+            (change, _, _, content) => (change, content, false),
+        };
+
+        // A kept file is left as is.
+        if !options.dry_run && change != Change::Kept {
+            match &content {
+                Some(bytes) => write_file(&app_file, bytes)?,
+                None => fs::remove_file(&app_file)
+                    .map_err(TadaError::fs(format!("Error removing {:?}", app_file)))?,
+            }
+        }
+
+        if edit {
+            open_in_editor(&app_file)?;
+        }
+
+        changes.push((path.clone(), change));
+    }
+
+    if !options.dry_run {
+        write_archive(&base_path, &theirs)?;
+    }
+
+    if changes.is_empty() {
+        let _ = outro("Already up to date!");
+        return Ok(());
+    }
+
+    let summary = changes
+        .iter()
+        .map(|(path, change)| format!("{:8}  {}", change.label(), path.display()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let _ = note("Changes", summary);
+
+    if changes
+        .iter()
+        .any(|(path, _)| path == Path::new("package.json"))
+    {
+        let _ = log::info("package.json changed, install the dependencies again");
+    }
+
+    let conflicts = changes
+        .iter()
+        .filter(|(_, change)| *change == Change::Conflict)
+        .count();
+    if conflicts > 0 {
+        let _ = log::warning(format!(
+            "{} conflict(s): resolve the markers, or pick a version for binary files",
+            conflicts
+        ));
+    }
+
+    let message = match options.dry_run {
+        true => format!("{} file(s) would change", changes.len()),
+        false => format!("{} file(s) changed", changes.len()),
+    };
+    let _ = outro(message);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(
+        base: Option<&str>,
+        ours: Option<&str>,
+        theirs: Option<&str>,
+    ) -> Option<(Change, Option<String>)> {
+        let [base, ours, theirs] = [base, ours, theirs].map(|x| x.map(|x| x.as_bytes().to_vec()));
+
+        merge_file(base.as_ref(), ours.as_ref(), theirs.as_ref())
+            .map(|(change, content)| (change, content.map(|x| String::from_utf8(x).unwrap())))
+    }
+
+    #[test]
+    fn files_untouched_in_the_app_follow_the_catalog() {
+        assert_eq!(merge(Some("a\n"), Some("a\n"), Some("a\n")), None);
+        assert_eq!(merge(Some("a\n"), Some("b\n"), Some("a\n")), None);
+        assert_eq!(merge(Some("a\n"), Some("b\n"), Some("b\n")), None);
+
+        assert_eq!(
+            merge(Some("a\n"), Some("a\n"), Some("b\n")),
+            Some((Change::Updated, Some("b\n".to_string())))
+        );
+        assert_eq!(
+            merge(None, None, Some("b\n")),
+            Some((Change::Added, Some("b\n".to_string())))
+        );
+        assert_eq!(
+            merge(Some("a\n"), Some("a\n"), None),
+            Some((Change::Removed, None))
+        );
+    }
+
+    #[test]
+    fn files_removed_on_one_side_and_changed_on_the_other_are_kept() {
+        assert_eq!(
+            merge(Some("a\n"), None, Some("b\n")),
+            Some((Change::Kept, None))
+        );
+        assert_eq!(
+            merge(Some("a\n"), Some("b\n"), None),
+            Some((Change::Kept, Some("b\n".to_string())))
+        );
+    }
+
+    #[test]
+    fn files_changed_on_both_sides_are_merged() {
+        assert_eq!(
+            merge(Some("a\nb\nc\n"), Some("A\nb\nc\n"), Some("a\nb\nC\n")),
+            Some((Change::Merged, Some("A\nb\nC\n".to_string())))
+        );
+
+        let (change, content) = merge(Some("a\n"), Some("b\n"), Some("c\n")).unwrap();
+        assert_eq!(change, Change::Conflict);
+        let content = content.unwrap();
+        assert!(content.contains("<<<<<<< ours\nb\n"));
+        assert!(content.contains(">>>>>>> theirs\n"));
+    }
+
+    #[test]
+    fn binary_files_changed_on_both_sides_keep_the_app_version() {
+        assert_eq!(
+            merge(Some("\0a"), Some("\0b"), Some("\0c")),
+            Some((Change::Conflict, Some("\0b".to_string())))
+        );
+    }
+
+    fn write(dir: &Path, files: &[(&str, &str)]) {
+        for (file, content) in files {
+            write_file(&dir.join(file), content.as_bytes()).unwrap();
+        }
+    }
+
+    fn details(dir: &Path, name: &str) -> Details {
+        Details {
+            name: name.rsplit('/').next().unwrap().to_string(),
+            path: dir.join(name).into_os_string(),
+            root: dir.as_os_str().to_os_string(),
+        }
+    }
+
+    #[test]
+    fn the_base_is_generated_with_the_entry_modes() {
+        let catalog = tempfile::tempdir().unwrap();
+        write(
+            catalog.path(),
+            &[
+                ("templates/react/package.json", r#"{"name": "react"}"#),
+                ("templates/react/README.md", "template\n"),
+                (
+                    "addons/docs/tada.json",
+                    r#"{"templates": ["react"], "entries": [{"input": "README.md", "output": ".", "mode": "overwrite"}]}"#,
+                ),
+                ("addons/docs/package.json", r#"{"name": "docs"}"#),
+                ("addons/docs/README.md", "addon\n"),
+            ],
+        );
+
+        // Generated with `--on-conflict keep`, into a directory with notes.
+        let app = tempfile::tempdir().unwrap();
+        write(
+            app.path(),
+            &[
+                ("package.json", "{}"),
+                ("README.md", "template\n"),
+                ("notes.txt", "mine"),
+            ],
+        );
+
+        let generation = GenerationJson {
+            template: "react".to_string(),
+            addons: vec!["docs".to_string()],
+            packageName: "app".to_string(),
+            skipLockfiles: false,
+            roots: vec!["/catalog".to_string()],
+            latestVersions: BTreeMap::new(),
+        };
+        record_generation(
+            app.path(),
+            &details(catalog.path(), "templates/react"),
+            &[details(catalog.path(), "addons/docs")],
+            &generation,
+        )
+        .unwrap();
+
+        let base = read_archive(&app.path().join(GENERATION_DIR).join(BASE_ARCHIVE)).unwrap();
+        assert_eq!(
+            base.keys().collect::<Vec<_>>(),
+            [Path::new("README.md"), Path::new("package.json")]
+        );
+        assert_eq!(base[Path::new("README.md")], b"addon\n");
+        assert_eq!(read_generation(app.path()).unwrap().roots, ["/catalog"]);
+    }
+
+    fn take_catalog_version(path: &Path, diff: Option<&str>) -> Result<Resolution> {
+        assert_eq!(path, Path::new("src/index.css"));
+        assert!(diff.unwrap().contains("+c"));
+        Ok(Resolution::Take)
+    }
+
+    #[test]
+    fn conflicts_are_settled_with_the_policy() {
+        let settle = |policy| {
+            let (change, content, _) = settle_conflict(
+                Path::new("src/index.css"),
+                b"b\n",
+                b"c\n",
+                b"<<<<<<< ours".to_vec(),
+                policy,
+            )
+            .unwrap();
+            (change, String::from_utf8(content).unwrap())
+        };
+
+        assert_eq!(
+            settle(ConflictPolicy::Mode),
+            (Change::Conflict, "<<<<<<< ours".to_string())
+        );
+        assert_eq!(
+            settle(ConflictPolicy::Keep),
+            (Change::Kept, "b\n".to_string())
+        );
+        assert_eq!(
+            settle(ConflictPolicy::Ask(take_catalog_version)),
+            (Change::Updated, "c\n".to_string())
+        );
+    }
+
+    #[test]
+    fn apps_without_a_manifest_cannot_be_upgraded() {
+        let app = tempfile::tempdir().unwrap();
+
+        let error = read_generation(app.path()).unwrap_err();
+
+        assert_eq!(error.code(), "ERR_TADA_UPGRADE");
+    }
+}
@@ -0,0 +1,469 @@
+#![deny(clippy::all)]
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use cliclack::{log, outro};
+
+use crate::error::{Result, TadaError};
+use crate::generate::{ignore_patterns, template_files};
+use crate::report_shadowed;
+use crate::utils::diagnostic::{parse_json_file, JsonDiagnostic};
+use crate::utils::fs::{
+    get_addons, get_filtered_addons, get_presets, get_templates, read_template_json, renamed,
+    validate_preset, AddonFileCopyType, Details, PackageJson, PresetJson, Shadowed, TadaJson,
+    TADA_JSON,
+};
+
+/// Files of every template in the new app, as `template_files` lists them.
+type TemplateFiles = BTreeMap<String, BTreeMap<PathBuf, PathBuf>>;
+
+/// Checks every template, addon and preset of the catalogs and returns the
+/// problems found, one message each. Addons are checked against the
+/// templates generation applies them to, and the entries hidden by one
+/// with the same name in a root of higher precedence end up in `shadowed`.
+pub fn check_catalogs(roots: &[PathBuf], shadowed: &mut Vec<Shadowed>) -> Result<Vec<String>> {
+    let mut problems: Vec<String> = Vec::new();
+
+    let mut templates: BTreeMap<String, Details> = BTreeMap::new();
+    for root in roots {
+        get_templates(root.as_os_str(), &mut templates, shadowed)?;
+    }
+
+    let mut files: TemplateFiles = BTreeMap::new();
+
+    for template in templates.values() {
+        let template_path = Path::new(&template.path);
+
+        if !template_path.is_dir() {
+            continue;
+        }
+
+        check_package_json(&template_path.join("package.json"), &mut problems);
+
+        let template_json = match read_template_json(template_path.as_os_str()) {
+            Ok(template_json) => template_json,
+            Err(e) => {
+                problems.push(e.to_string());
+                continue;
+            }
+        };
+
+        let template_files = match template_files(template, &ignore_patterns(false)) {
+            Ok(template_files) => template_files,
+            Err(e) => {
+                problems.push(format!("{}: {}", template_path.display(), e));
+                continue;
+            }
+        };
+
+        // Names of the template files and directories, before renaming.
+        let names = template_files
+            .values()
+            .filter_map(|x| x.strip_prefix(template_path).ok())
+            .flat_map(|x| x.iter().map(OsString::from))
+            .collect::<BTreeSet<_>>();
+        check_renames(
+            &template_path.join(TADA_JSON),
+            &template_json.renames,
+            &names,
+            "the template",
+            &mut problems,
+        );
+
+        files.insert(template.name.clone(), template_files);
+    }
+
+    // Every addon, including the ones compatible with no template, then
+    // the templates each one is applied to. Shadowing depends on the
+    // template, as during generation.
+    let mut invalid: Vec<JsonDiagnostic> = Vec::new();
+    let mut all_addons: BTreeMap<String, Details> = BTreeMap::new();
+    for root in roots {
+        get_addons(
+            root.as_os_str(),
+            &mut all_addons,
+            &mut Vec::new(),
+            &mut invalid,
+        )?;
+    }
+    problems.extend(invalid.iter().map(|x| x.to_string()));
+
+    let mut addons: BTreeMap<OsString, (Details, Vec<&Details>)> = all_addons
+        .into_values()
+        .map(|x| (x.path.clone(), (x, Vec::new())))
+        .collect();
+
+    for template in templates.values() {
+        let mut compatible_addons: BTreeMap<String, Details> = BTreeMap::new();
+        let mut template_shadowed: Vec<Shadowed> = Vec::new();
+        for root in roots {
+            get_filtered_addons(
+                root.as_os_str(),
+                template.name.clone(),
+                &mut compatible_addons,
+                &mut template_shadowed,
+                &mut Vec::new(),
+            )?;
+        }
+
+        for entry in template_shadowed {
+            if !shadowed
+                .iter()
+                .any(|x| x.name == entry.name && x.root == entry.root && x.by == entry.by)
+            {
+                shadowed.push(entry);
+            }
+        }
+
+        for addon in compatible_addons.into_values() {
+            addons
+                .entry(addon.path.clone())
+                .or_insert_with(|| (addon, Vec::new()))
+                .1
+                .push(template);
+        }
+    }
+
+    for (addon, compatible_templates) in addons.values() {
+        check_addon(
+            Path::new(&addon.path),
+            &templates,
+            compatible_templates,
+            &files,
+            &mut problems,
+        );
+    }
+
+    let mut presets: BTreeMap<String, Details> = BTreeMap::new();
+    for root in roots {
+        get_presets(root.as_os_str(), &mut presets, shadowed)?;
+    }
+
+    for preset in presets.values() {
+        match parse_json_file::<PresetJson>(&preset.path) {
+            Ok(preset_json) => {
+                if let Err(e) =
+                    validate_preset(Path::new(&preset.path), &preset_json, &templates, roots)
+                {
+                    problems.push(e.to_string());
+                }
+            }
+            Err(e) => problems.push(e.to_string()),
+        }
+    }
+
+    Ok(problems)
+}
+
+fn check_package_json(path: &Path, problems: &mut Vec<String>) {
+    if !path.is_file() {
+        problems.push(format!("{}: missing", path.display()));
+        return;
+    }
+
+    if let Err(e) = parse_json_file::<PackageJson>(path.as_os_str()) {
+        problems.push(e.to_string());
+    }
+}
+
+/// Every key of `renames` must be the name of a file or directory that is
+/// copied, `names`.
+fn check_renames(
+    tada_json_path: &Path,
+    renames: &BTreeMap<String, String>,
+    names: &BTreeSet<OsString>,
+    copied_by: &str,
+    problems: &mut Vec<String>,
+) {
+    for name in renames.keys() {
+        if !names.contains(&OsString::from(name)) {
+            problems.push(format!(
+                "{}: renamed `{}` is not copied by {}",
+                tada_json_path.display(),
+                name,
+                copied_by
+            ));
+        }
+    }
+}
+
+fn is_binary(path: &Path) -> bool {
+    fs::read(path)
+        .map(|x| content_inspector::inspect(&x).is_binary())
+        .unwrap_or(false)
+}
+
+/// Lists the files of a directory entry like `copy_addon_dir` copies them,
+/// keyed by their path in the new app, with the names met on the way.
+fn list_entry_dir(
+    from: &Path,
+    to: &Path,
+    renames: &BTreeMap<String, String>,
+    files: &mut BTreeMap<PathBuf, PathBuf>,
+    names: &mut BTreeSet<OsString>,
+) {
+    let Ok(entries) = fs::read_dir(from) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let destination = to.join(renamed(&entry.file_name(), renames));
+        names.insert(entry.file_name());
+
+        if path.is_dir() {
+            list_entry_dir(&path, &destination, renames, files, names);
+        } else {
+            files.insert(destination, path);
+        }
+    }
+}
+
+fn check_addon(
+    addon_path: &Path,
+    templates: &BTreeMap<String, Details>,
+    compatible_templates: &[&Details],
+    template_files: &TemplateFiles,
+    problems: &mut Vec<String>,
+) {
+    let tada_json_path = addon_path.join(TADA_JSON);
+
+    // Invalid ones are reported while listing the addons.
+    let Ok(tada_json) = parse_json_file::<TadaJson>(tada_json_path.as_os_str()) else {
+        return;
+    };
+
+    check_package_json(&addon_path.join("package.json"), problems);
+
+    for template_name in &tada_json.templates {
+        if template_name != "all" && !templates.contains_key(template_name) {
+            problems.push(format!(
+                "{}: template `{}` not found",
+                tada_json_path.display(),
+                template_name
+            ));
+        }
+    }
+
+    let renames = tada_json.renames.clone().unwrap_or_default();
+    let mut names: BTreeSet<OsString> = BTreeSet::new();
+
+    for entry in &tada_json.entries {
+        let entry_source = addon_path.join(&entry.input);
+
+        let Some(file_name) = entry_source.file_name().filter(|_| entry_source.exists()) else {
+            problems.push(format!(
+                "{}: entry input `{}` does not exist",
+                tada_json_path.display(),
+                entry.input
+            ));
+            continue;
+        };
+        names.insert(file_name.to_os_string());
+
+        let mode = match &entry.mode {
+            Some(AddonFileCopyType::Prepend) => "prepend",
+            Some(AddonFileCopyType::Append) => "append",
+            Some(AddonFileCopyType::Overwrite) => "overwrite",
+            Some(AddonFileCopyType::SkipIfExists) => "skip_if_exists",
+            None => "merge",
+        };
+
+        // Files of the entry, keyed by their path in the new app.
+        let output = Path::new(&entry.output)
+            .components()
+            .filter(|x| !matches!(x, Component::CurDir))
+            .collect::<PathBuf>();
+        let target = output.join(renamed(file_name, &renames));
+        let is_dir = entry_source.is_dir();
+
+        let mut files: BTreeMap<PathBuf, PathBuf> = BTreeMap::new();
+        match is_dir {
+            true => list_entry_dir(&entry_source, &target, &renames, &mut files, &mut names),
+            false => {
+                files.insert(target, entry_source.clone());
+            }
+        }
+
+        let merged = matches!(mode, "prepend" | "append" | "merge");
+
+        if matches!(mode, "prepend" | "append") && !is_dir && is_binary(&entry_source) {
+            problems.push(format!(
+                "{}: `{}` entry `{}` is a binary file, use the `overwrite` or `skip_if_exists` mode",
+                tada_json_path.display(),
+                mode,
+                entry.input
+            ));
+        }
+
+        for template in compatible_templates {
+            let Some(template_files) = template_files.get(&template.name) else {
+                continue;
+            };
+
+            for (target, source) in &files {
+                let existing = template_files.get(target);
+                let is_template_dir =
+                    existing.is_none() && template_files.keys().any(|x| x.starts_with(target));
+
+                let problem = match existing {
+                    _ if is_template_dir => Some("is a directory in"),
+                    Some(_) if is_dir => Some("cannot be replaced by a directory entry, it is in"),
+                    None if matches!(mode, "prepend" | "append") => Some("is missing from"),
+                    Some(existing) if merged && (is_binary(existing) || is_binary(source)) => Some(
+                        "is a binary file, use the `overwrite` or `skip_if_exists` mode, it is in",
+                    ),
+                    _ => None,
+                };
+
+                if let Some(problem) = problem {
+                    problems.push(format!(
+                        "{}: `{}` entry `{}` targets `{}`, which {} template `{}`",
+                        tada_json_path.display(),
+                        mode,
+                        entry.input,
+                        target.display(),
+                        problem,
+                        template.name
+                    ));
+                }
+            }
+        }
+    }
+
+    check_renames(&tada_json_path, &renames, &names, "the entries", problems);
+}
+
+/// Reports the problems found in the catalogs, failing when there is any.
+pub fn validate_catalogs(roots: &[PathBuf]) -> Result<()> {
+    let mut shadowed: Vec<Shadowed> = Vec::new();
+    let problems = check_catalogs(roots, &mut shadowed)?;
+
+    for root in roots {
+        let _ = log::step(format!("Checked {}", root.display()));
+    }
+
+    report_shadowed(&mut shadowed);
+
+    if problems.is_empty() {
+        let _ = outro("Catalogs are valid!");
+        return Ok(());
+    }
+
+    for problem in &problems {
+        let _ = log::error(problem);
+    }
+
+    Err(TadaError::Catalog(format!(
+        "{} problem(s) found",
+        problems.len()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: PathBuf, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn prepend_targets_follow_renames() {
+        let root = tempfile::tempdir().unwrap();
+        let template = root.path().join("templates/react");
+        let addon = root.path().join("addons/git");
+
+        write(template.join("package.json"), r#"{"name": "react"}"#);
+        write(template.join(".gitignore"), "dist\n");
+        write(template.join("_npmrc"), "");
+        write(
+            template.join(TADA_JSON),
+            r#"{"renames": {"env.example": ".env"}}"#,
+        );
+        write(template.join("env.example"), "");
+
+        write(addon.join("package.json"), r#"{"name": "git"}"#);
+        write(addon.join("_gitignore"), "coverage\n");
+        write(addon.join("npmrc"), "");
+        write(addon.join("dotenv"), "");
+        write(addon.join("readme"), "");
+        write(
+            addon.join(TADA_JSON),
+            r#"{
+                "templates": ["react"],
+                "renames": {"npmrc": ".npmrc", "dotenv": ".env"},
+                "entries": [
+                    {"mode": "append", "input": "_gitignore", "output": "."},
+                    {"mode": "append", "input": "npmrc", "output": "."},
+                    {"mode": "prepend", "input": "dotenv", "output": "."},
+                    {"mode": "append", "input": "readme", "output": "."}
+                ]
+            }"#,
+        );
+
+        let problems = check_catalogs(&[root.path().to_path_buf()], &mut Vec::new()).unwrap();
+
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains("targets `readme`"));
+    }
+
+    #[test]
+    fn addons_are_checked_as_generation_applies_them() {
+        let high = tempfile::tempdir().unwrap();
+        let low = tempfile::tempdir().unwrap();
+        let template = high.path().join("templates/react");
+        let assets = high.path().join("addons/assets");
+
+        write(template.join("package.json"), r#"{"name": "react"}"#);
+        write(template.join("src/App.tsx"), "");
+        write(template.join("logo.png"), "\0png");
+        write(
+            template.join(TADA_JSON),
+            r#"{"renames": {"missing.txt": "found.txt"}}"#,
+        );
+
+        write(assets.join("package.json"), r#"{"name": "assets"}"#);
+        write(assets.join("logo.png"), "\0other");
+        write(assets.join("src/App.tsx"), "");
+        write(
+            assets.join(TADA_JSON),
+            r#"{
+                "templates": ["react"],
+                "renames": {"unused": "used"},
+                "entries": [
+                    {"input": "logo.png", "output": "."},
+                    {"input": "src", "output": "."}
+                ]
+            }"#,
+        );
+
+        // Shadowed by `assets` of the first root, so never applied.
+        write(low.path().join("addons/assets/package.json"), "{}");
+        write(
+            low.path().join("addons/assets").join(TADA_JSON),
+            r#"{"templates": ["react"], "entries": [{"input": "gone", "output": "."}]}"#,
+        );
+
+        let mut shadowed: Vec<Shadowed> = Vec::new();
+        let problems = check_catalogs(
+            &[high.path().to_path_buf(), low.path().to_path_buf()],
+            &mut shadowed,
+        )
+        .unwrap();
+
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+        assert!(problems[0].contains("renamed `missing.txt` is not copied by the template"));
+        assert!(problems[1]
+            .contains("`merge` entry `logo.png` targets `logo.png`, which is a binary file"));
+        assert!(problems[2].contains("`merge` entry `src` targets `src/App.tsx`, which cannot be replaced by a directory entry"));
+        assert!(problems[3].contains("renamed `unused` is not copied by the entries"));
+
+        assert_eq!(shadowed.len(), 1);
+        assert_eq!(shadowed[0].name, "assets");
+    }
+}
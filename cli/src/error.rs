@@ -0,0 +1,94 @@
+#![deny(clippy::all)]
+
+use std::error::Error;
+use std::io;
+use std::path::PathBuf;
+
+use super::utils::diagnostic::JsonDiagnostic;
+
+/// Every failure of the cli. They are propagated up to `main`, which
+/// reports them once.
+#[derive(Debug, thiserror::Error)]
+pub enum TadaError {
+    /// A template, addon, preset or catalog root that is missing or unusable.
+    #[error("{0}")]
+    Catalog(String),
+    /// A `tada.json`, `package.json`, preset or config file that is invalid.
+    #[error(transparent)]
+    Manifest(#[from] JsonDiagnostic),
+    /// An addon entry that cannot be merged into an existing file.
+    #[error("Error merging {}: {message}", path.display())]
+    Merge { path: PathBuf, message: String },
+    #[error("{context}: {source}")]
+    Fs {
+        context: String,
+        #[source]
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// The user cancelled a prompt.
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("{0}")]
+    Install(String),
+    /// A target directory that cannot be generated into.
+    #[error("{0}")]
+    Target(String),
+    /// A failure while applying an addon. It keeps the code of `source`.
+    #[error("Error applying addon `{name}`: {source}")]
+    Addon {
+        name: String,
+        #[source]
+        source: Box<TadaError>,
+    },
+    /// An app that cannot be upgraded, e.g. without a generation manifest.
+    #[error("{0}")]
+    Upgrade(String),
+    /// Command line arguments that clap refused, with its usage message.
+    #[error("{0}")]
+    Args(String),
+    /// A template or addon hook that failed, with the end of its output.
+    #[error("Hook `{command}` failed:\n{output}")]
+    Hook { command: String, output: String },
+}
+
+impl TadaError {
+    /// `code` of the JS `Error` thrown to napi callers.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TadaError::Catalog(_) => "ERR_TADA_CATALOG",
+            TadaError::Manifest(_) => "ERR_TADA_MANIFEST",
+            TadaError::Merge { .. } => "ERR_TADA_MERGE",
+            TadaError::Fs { .. } => "ERR_TADA_FS",
+            TadaError::Cancelled => "ERR_TADA_CANCELLED",
+            TadaError::Install(_) => "ERR_TADA_INSTALL",
+            TadaError::Target(_) => "ERR_TADA_TARGET",
+            TadaError::Hook { .. } => "ERR_TADA_HOOK",
+            TadaError::Upgrade(_) => "ERR_TADA_UPGRADE",
+            TadaError::Args(_) => "ERR_TADA_ARGS",
+            TadaError::Addon { source, .. } => source.code(),
+        }
+    }
+
+    /// For `map_err`, e.g. `.map_err(TadaError::fs("Error reading file"))`.
+    pub fn fs<E>(context: impl Into<String>) -> impl FnOnce(E) -> TadaError
+    where
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let context = context.into();
+
+        move |source| TadaError::Fs {
+            context,
+            source: source.into(),
+        }
+    }
+
+    /// Prompts fail with `Interrupted` when the user presses Ctrl+C or Esc.
+    pub fn prompt(error: io::Error) -> TadaError {
+        match error.kind() {
+            io::ErrorKind::Interrupted => TadaError::Cancelled,
+            _ => TadaError::fs("Error reading the answer")(error),
+        }
+    }
+}
+
+pub type Result<T, E = TadaError> = std::result::Result<T, E>;
@@ -0,0 +1,416 @@
+#![deny(clippy::all)]
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use super::error::{Result, TadaError};
+use super::utils::conflict::ConflictPolicy;
+use super::utils::deps::{dedupe_dependencies, DependencyKind, DependencyMove};
+use super::utils::diagnostic::parse_json_file;
+use super::utils::fs::{
+    copy_addon_items, read_template_json, renamed, Details, PackageJson, TadaJson, TemplateJson,
+    TADAIGNORE, TADA_JSON,
+};
+use super::utils::name::slugify;
+use super::utils::workspace::LOCKFILES;
+
+pub const IGNORE: [&str; 4] = ["node_modules", ".turbo", "dist", ".DS_Store"];
+
+/// The `package.json` of the new app, with the maps every addon merges into.
+#[derive(Debug)]
+pub struct Manifest {
+    pub package_json: PackageJson,
+    pub dependencies: BTreeMap<String, String>,
+    pub dev_dependencies: BTreeMap<String, String>,
+    pub scripts: BTreeMap<String, String>,
+    pub dependency_kinds: BTreeMap<String, DependencyKind>,
+}
+
+impl Manifest {
+    pub fn read(new_app_path: &Path) -> Result<Manifest> {
+        let package_json_path = new_app_path.join("package.json");
+        let mut package_json: PackageJson = parse_json_file(package_json_path.as_os_str())?;
+
+        Ok(Manifest {
+            dependencies: package_json.dependencies.take().unwrap_or_default(),
+            dev_dependencies: package_json.devDependencies.take().unwrap_or_default(),
+            scripts: package_json.scripts.take().unwrap_or_default(),
+            dependency_kinds: BTreeMap::new(),
+            package_json,
+        })
+    }
+
+    pub fn dedupe(&mut self) -> Vec<DependencyMove> {
+        dedupe_dependencies(
+            &mut self.dependencies,
+            &mut self.dev_dependencies,
+            &self.dependency_kinds,
+        )
+    }
+
+    pub fn write(&self, new_app_path: &Path, name: String) -> Result<()> {
+        let mut package_json = self.package_json.clone();
+        package_json.name = name;
+        package_json.scripts = Some(self.scripts.clone());
+        package_json.dependencies = Some(self.dependencies.clone());
+        package_json.devDependencies = Some(self.dev_dependencies.clone());
+
+        let new_package_json_string = serde_json::to_string_pretty(&package_json)
+            .map_err(TadaError::fs("Error serializing package.json"))?;
+
+        fs::write(new_app_path.join("package.json"), new_package_json_string)
+            .map_err(TadaError::fs("Error writing `package.json` file"))
+    }
+}
+
+/// Patterns, in `.gitignore` syntax, of the template files that are never
+/// copied. Lockfiles are skipped too when generating into a workspace.
+pub fn ignore_patterns(skip_lockfiles: bool) -> Vec<String> {
+    let mut patterns = IGNORE.map(|x| x.to_string()).to_vec();
+    patterns.push(format!("/{}", TADA_JSON));
+    patterns.push(format!("/{}", TADAIGNORE));
+
+    if skip_lockfiles {
+        patterns.extend(LOCKFILES.map(|x| format!("/{}", x)));
+    }
+
+    patterns
+}
+
+/// Matches the template files that are not copied: `patterns` first, then
+/// the template `.gitignore` when its metadata opts in, then its
+/// `.tadaignore`, so a later `!pattern` can bring a file back.
+fn template_ignore(
+    template_path: &Path,
+    template_json: &TemplateJson,
+    patterns: &[String],
+) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(template_path);
+
+    for pattern in patterns {
+        builder.add_line(None, pattern).map_err(|e| {
+            TadaError::Catalog(format!("Invalid ignore pattern `{}`: {}", pattern, e))
+        })?;
+    }
+
+    let mut files = Vec::new();
+    if template_json.gitignore {
+        // Published templates ship it as `_gitignore`.
+        files.push(template_path.join(".gitignore"));
+        files.push(template_path.join("_gitignore"));
+    }
+    files.push(template_path.join(TADAIGNORE));
+
+    for file in files.iter().filter(|x| x.is_file()) {
+        if let Some(e) = builder.add(file) {
+            return Err(TadaError::Catalog(format!("{}: {}", file.display(), e)));
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| TadaError::Catalog(format!("{}: {}", template_path.display(), e)))
+}
+
+fn copy_dir(
+    from: &Path,
+    to: &Path,
+    ignore: &Gitignore,
+    renames: &BTreeMap<String, String>,
+) -> Result<()> {
+    fs::create_dir_all(to).map_err(TadaError::fs(format!("Error creating directory: {:?}", to)))?;
+
+    let entries = fs::read_dir(from).map_err(TadaError::fs(format!("Error reading {:?}", from)))?;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_dir = path.is_dir();
+
+        if ignore.matched(&path, is_dir).is_ignore() {
+            continue;
+        }
+
+        let destination = to.join(renamed(&entry.file_name(), renames));
+
+        if is_dir {
+            copy_dir(&path, &destination, ignore, renames)?;
+        } else {
+            fs::copy(&path, &destination)
+                .map_err(TadaError::fs(format!("Error copying {:?}", path)))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies the template into the new app, skipping the files matched by
+/// `patterns`, the template `.tadaignore` and, optionally, its `.gitignore`.
+/// `_gitignore`-like files and the ones in the template `renames` are
+/// renamed on the way.
+pub fn copy_template(template: &Details, new_app_path: &Path, patterns: &[String]) -> Result<()> {
+    let template_path = Path::new(&template.path);
+    let template_json = read_template_json(template_path.as_os_str())?;
+    let ignore = template_ignore(template_path, &template_json, patterns)?;
+
+    copy_dir(template_path, new_app_path, &ignore, &template_json.renames)
+}
+
+fn list_dir_files(
+    from: &Path,
+    to: &Path,
+    ignore: &Gitignore,
+    renames: &BTreeMap<String, String>,
+    files: &mut BTreeMap<PathBuf, PathBuf>,
+) -> Result<()> {
+    let entries = fs::read_dir(from).map_err(TadaError::fs(format!("Error reading {:?}", from)))?;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_dir = path.is_dir();
+
+        if ignore.matched(&path, is_dir).is_ignore() {
+            continue;
+        }
+
+        let destination = to.join(renamed(&entry.file_name(), renames));
+
+        if is_dir {
+            list_dir_files(&path, &destination, ignore, renames, files)?;
+        } else {
+            files.insert(destination, path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Files `copy_template` copies, keyed by their path in the new app, with
+/// their path in the template.
+pub fn template_files(
+    template: &Details,
+    patterns: &[String],
+) -> Result<BTreeMap<PathBuf, PathBuf>> {
+    let template_path = Path::new(&template.path);
+    let template_json = read_template_json(template_path.as_os_str())?;
+    let ignore = template_ignore(template_path, &template_json, patterns)?;
+
+    let mut files: BTreeMap<PathBuf, PathBuf> = BTreeMap::new();
+    list_dir_files(
+        template_path,
+        Path::new(""),
+        &ignore,
+        &template_json.renames,
+        &mut files,
+    )?;
+
+    Ok(files)
+}
+
+/// Copies the addon entries into the new app and merges its `package.json`
+/// into `manifest`.
+/// `policy` decides what happens to the files the addon would replace.
+pub fn apply_addon(
+    addon: &Details,
+    new_app_path: &Path,
+    manifest: &mut Manifest,
+    policy: ConflictPolicy,
+) -> Result<()> {
+    merge_addon(addon, new_app_path, manifest, policy).map_err(|e| match e {
+        TadaError::Cancelled => e,
+        e => TadaError::Addon {
+            name: addon.name.clone(),
+            source: Box::new(e),
+        },
+    })
+}
+
+fn merge_addon(
+    addon: &Details,
+    new_app_path: &Path,
+    manifest: &mut Manifest,
+    policy: ConflictPolicy,
+) -> Result<()> {
+    let addon_path = Path::new(&addon.path);
+
+    let addon_tada_json_path = addon_path.join(TADA_JSON);
+    let addon_package_json_path = addon_path.join("package.json");
+
+    let addon_tada_json: TadaJson = parse_json_file(addon_tada_json_path.as_os_str())?;
+    let addon_package_json: PackageJson = parse_json_file(addon_package_json_path.as_os_str())?;
+
+    if let Some(dependencies_map) = addon_package_json.dependencies {
+        manifest.dependencies.extend(dependencies_map);
+    }
+
+    if let Some(dev_dependencies_map) = addon_package_json.devDependencies {
+        manifest.dev_dependencies.extend(dev_dependencies_map);
+    }
+
+    if let Some(scripts_map) = addon_package_json.scripts {
+        manifest.scripts.extend(scripts_map);
+    }
+
+    if let Some(kinds_map) = addon_tada_json.dependencyKinds {
+        manifest.dependency_kinds.extend(kinds_map);
+    }
+
+    let renames = addon_tada_json.renames.unwrap_or_default();
+
+    for addon_entry in &addon_tada_json.entries {
+        let addon_entry_source = addon_path.join(OsString::from(&addon_entry.input).as_os_str());
+        let addon_entry_destination =
+            new_app_path.join(OsString::from(&addon_entry.output).as_os_str());
+
+        let addon_entry_os_source = addon_entry_source.as_os_str();
+        let addon_entry_os_destination = addon_entry_destination.as_os_str();
+
+        copy_addon_items(
+            &[addon_entry_os_source],
+            addon_entry_os_destination,
+            &addon_entry.mode,
+            &renames,
+            policy,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Generates an app without prompting: copies the template, applies the
+/// addons in order and writes the merged `package.json`.
+/// `package_name` defaults to the directory name made valid.
+pub fn generate(
+    template: &Details,
+    addons: &[Details],
+    new_app_path: &Path,
+    ignore: &[String],
+    policy: ConflictPolicy,
+    package_name: Option<&str>,
+) -> Result<Manifest> {
+    fs::create_dir_all(new_app_path).map_err(TadaError::fs(format!(
+        "Error creating directory: {:?}",
+        new_app_path
+    )))?;
+
+    copy_template(template, new_app_path, ignore)?;
+
+    let mut manifest = Manifest::read(new_app_path)?;
+
+    for addon in addons {
+        apply_addon(addon, new_app_path, &mut manifest, policy)?;
+    }
+
+    manifest.dedupe();
+
+    let name = match package_name {
+        Some(name) => name.to_string(),
+        None => new_app_path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .and_then(slugify)
+            .unwrap_or_else(|| template.name.clone()),
+    };
+    manifest.write(new_app_path, name)?;
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(files: &[&str]) -> (tempfile::TempDir, Details) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("templates/react");
+
+        for file in files {
+            let file = path.join(file);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, r#"{"name": "react"}"#).unwrap();
+        }
+
+        let details = Details {
+            name: "react".to_string(),
+            path: path.into_os_string(),
+            root: dir.path().as_os_str().to_os_string(),
+        };
+
+        (dir, details)
+    }
+
+    fn copied(template: &Details, patterns: &[String]) -> Vec<String> {
+        let app = tempfile::tempdir().unwrap();
+        copy_template(template, app.path(), patterns).unwrap();
+
+        let mut files = Vec::new();
+        crate::utils::fs::list_files(app.path(), &mut files).unwrap();
+
+        let mut files = files
+            .iter()
+            .map(|x| {
+                x.strip_prefix(app.path())
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn metadata_and_build_output_are_not_copied() {
+        let (_dir, template) = template(&[
+            "tada.json",
+            "package.json",
+            "pnpm-lock.yaml",
+            "node_modules/react/index.js",
+            "dist/index.js",
+            "src/tada.json",
+        ]);
+
+        assert_eq!(
+            copied(&template, &ignore_patterns(false)),
+            ["package.json", "pnpm-lock.yaml", "src/tada.json"]
+        );
+    }
+
+    #[test]
+    fn addon_errors_name_the_addon() {
+        let (dir, template) = template(&["package.json"]);
+        let addon = Details {
+            name: "tailwindcss".to_string(),
+            path: dir.path().join("addons/tailwindcss").into_os_string(),
+            root: template.root.clone(),
+        };
+        let mut manifest = Manifest::read(Path::new(&template.path)).unwrap();
+
+        let error =
+            apply_addon(&addon, dir.path(), &mut manifest, ConflictPolicy::Mode).unwrap_err();
+
+        assert_eq!(error.code(), "ERR_TADA_MANIFEST");
+        assert!(error
+            .to_string()
+            .starts_with("Error applying addon `tailwindcss`: "));
+    }
+
+    #[test]
+    fn lockfiles_are_skipped_in_workspaces() {
+        let (_dir, template) = template(&[
+            "package.json",
+            "pnpm-lock.yaml",
+            "package-lock.json",
+            "yarn.lock",
+            "src/yarn.lock",
+        ]);
+
+        assert_eq!(
+            copied(&template, &ignore_patterns(true)),
+            ["package.json", "src/yarn.lock"]
+        );
+    }
+}
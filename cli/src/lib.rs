@@ -4,32 +4,57 @@
 extern crate napi_derive;
 extern crate fs_extra;
 
+mod args;
+mod commands;
+mod error;
+mod generate;
 mod prompts;
 mod utils;
 
-use fs_extra::copy_items;
-use fs_extra::dir::CopyOptions;
 use relative_path::RelativePath;
 use std::collections::BTreeMap;
 use std::env;
 use std::ffi::OsString;
-use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use anyhow::Context;
-use cliclack::{intro, outro, outro_cancel, spinner, ProgressBar};
+use cliclack::{intro, log, outro, outro_cancel, spinner, ProgressBar};
 use dotenv::dotenv;
 
+use args::{parse_args, Args, Command};
+use commands::dev::{dev_addons, DevOptions};
+use commands::snapshot::{snapshot_scenarios, SnapshotOptions};
+use commands::test::{test_catalogs, TestOptions};
+use commands::upgrade::{
+    read_generation, record_generation, upgrade_app, GenerationJson, LatestVersion, UpgradeOptions,
+};
+use commands::validate::validate_catalogs;
+use error::{Result, TadaError};
+use generate::{apply_addon, copy_template, ignore_patterns, Manifest};
+use utils::config::{load_config, Config, CONFIG_FILE};
+use utils::conflict::ConflictPolicy;
+use utils::diagnostic::{parse_json_file, JsonDiagnostic};
 use utils::fs::{
-    copy_addon_items, get_filtered_addons, get_items_in_template, get_templates, read_json_file,
-    Details, PackageJson, TadaJson,
+    get_filtered_addons, get_presets, get_templates, read_json_file, read_template_json,
+    validate_preset, Details, PresetJson, Shadowed, TadaJson, TADA_JSON,
 };
+use utils::git::{Repository, Skipped};
+use utils::hooks::{collect_hooks, run_hook, Hook, Stage};
+use utils::name::{app_dir, package_name};
 use utils::pm::install_dependencies;
-
-use prompts::{prompt_app_path, prompt_install_deps, prompt_select_addons, prompt_select_template};
+use utils::registry::{Registry, RegistryReport};
+use utils::source::{split_roots, Source};
+use utils::target::{empty_dir, is_non_empty, is_protected, ExistingTarget};
+use utils::workspace::Workspace;
+
+use prompts::{
+    prompt_app_path, prompt_empty_target, prompt_existing_target, prompt_git_init,
+    prompt_install_deps, prompt_package_name, prompt_resolve_conflict,
+    prompt_resolve_upgrade_conflict, prompt_run_hooks, prompt_select_addons, prompt_select_preset,
+    prompt_select_template,
+};
 
 const ENV_VAR: &str = "TADA_APP";
-const IGNORE: [&str; 3] = ["node_modules", ".turbo", "dist"];
 
 pub fn start_spinner(message: &str) -> ProgressBar {
     let spinner = spinner();
@@ -39,218 +64,735 @@ pub fn start_spinner(message: &str) -> ProgressBar {
     spinner
 }
 
+/// Refuses the home directory and the filesystem root, and asks what to do
+/// with a non-empty directory unless `force` is set. Returns whether it
+/// should be emptied, which waits for the other answers.
+fn prepare_target(path: &Path, force: bool) -> Result<bool> {
+    if is_protected(path) {
+        return Err(TadaError::Target(format!(
+            "Refusing to generate into {}, choose a new directory",
+            path.display()
+        )));
+    }
+
+    if force || !is_non_empty(path) {
+        return Ok(false);
+    }
+
+    match prompt_existing_target(path)? {
+        ExistingTarget::Cancel => Err(TadaError::Cancelled),
+        ExistingTarget::Merge => Ok(false),
+        ExistingTarget::Empty => match prompt_empty_target(path)? {
+            true => Ok(true),
+            false => Err(TadaError::Cancelled),
+        },
+    }
+}
+
+fn run_hooks(hooks: &[Hook], stage: Stage, app_path: &Path) -> Result<()> {
+    for hook in hooks.iter().filter(|x| x.stage == stage) {
+        let spinner = start_spinner(&format!("Running {}...", hook));
+
+        run_hook(hook, app_path).inspect_err(|_| spinner.error(format!("{} failed", hook)))?;
+
+        spinner.stop(format!("Ran {}", hook));
+    }
+
+    Ok(())
+}
+
+/// Git is optional: when it is missing or fails, the app is still generated
+/// and the repository is left for the user to create.
+fn init_repository(path: &Path) -> Option<Repository> {
+    match Repository::check(path) {
+        Ok(_) => {}
+        Err(Skipped::GitMissing) => {
+            let _ = log::warning("git not found, skipping the repository");
+            return None;
+        }
+        Err(Skipped::InsideRepository(root)) => {
+            let _ = log::info(format!(
+                "Already inside the repository at {}, skipping git init",
+                root.display()
+            ));
+            return None;
+        }
+    }
+
+    Repository::init(path)
+        .inspect_err(|e| {
+            let _ = log::warning(format!("Unable to initialize the repository: {}", e));
+        })
+        .ok()
+}
+
+/// Stops committing after the first failure, e.g. when no git identity is
+/// configured.
+fn commit(repository: &mut Option<Repository>, message: &str) {
+    let Some(repo) = repository else {
+        return;
+    };
+
+    match repo.commit_all(message) {
+        Ok(true) => {
+            let _ = log::step(format!("Committed \"{}\"", message));
+        }
+        Ok(false) => {}
+        Err(e) => {
+            let _ = log::warning(format!("Unable to commit: {}", e));
+            *repository = None;
+        }
+    }
+}
+
+pub fn report_shadowed(shadowed: &mut Vec<Shadowed>) {
+    for entry in shadowed.drain(..) {
+        let _ = log::warning(format!(
+            "`{}` from {} is shadowed by {}",
+            entry.name,
+            entry.root.to_string_lossy(),
+            entry.by.to_string_lossy()
+        ));
+    }
+}
+
+/// Makes every catalog root available on disk, fetching the remote ones.
+fn fetch_roots(root_specs: &[String], args: &Args) -> Result<Vec<PathBuf>> {
+    let mut tada_app_roots: Vec<PathBuf> = Vec::new();
+    let mut fetch_spinner: Option<ProgressBar> = None;
+
+    for spec in root_specs {
+        let source = Source::parse(spec);
+
+        if !matches!(source, Source::Local(_)) && fetch_spinner.is_none() {
+            fetch_spinner = Some(start_spinner("Fetching catalogs..."));
+        }
+
+        match source.fetch(args.offline, Duration::from_secs(args.fetch_timeout)) {
+            Ok(fetched) => {
+                if let Some(warning) = fetched.warning {
+                    let _ = log::warning(warning);
+                }
+
+                tada_app_roots.push(fetched.path);
+            }
+            Err(e) => {
+                if let Some(spinner) = &fetch_spinner {
+                    spinner.error("Unable to fetch catalogs");
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    if let Some(spinner) = fetch_spinner {
+        spinner.stop("Catalogs ready!");
+    }
+
+    Ok(tada_app_roots)
+}
+
+/// A local root as an absolute path, so that it still resolves from
+/// another directory. Remote roots are kept as given.
+fn absolute_root(spec: &str) -> String {
+    match Source::parse(spec) {
+        Source::Local(path) => path
+            .canonicalize()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_else(|_| spec.to_string()),
+        _ => spec.to_string(),
+    }
+}
+
+/// Errors are reported once, here: the cli gets an `outro_cancel` and napi
+/// callers a JS `Error` with the `code` of the error.
 #[napi]
-fn main() {
+fn main(args: Vec<String>) -> napi::Result<(), &'static str> {
+    run(args).map_err(|e| {
+        let _ = outro_cancel(e.to_string());
+        napi::Error::new(e.code(), e.to_string())
+    })
+}
+
+fn run(args: Vec<String>) -> Result<()> {
     dotenv().ok();
 
+    let Some(args) = parse_args(args)? else {
+        return Ok(());
+    };
+
     let _ = intro("create-tada-app");
 
-    let tada_app_path: OsString;
-    if let Ok(path) = env::var(ENV_VAR) {
-        tada_app_path = OsString::from(path);
-    } else {
-        let _ = outro_cancel("Error reading environment variable");
-        std::process::exit(1);
+    let cwd = env::current_dir().map_err(TadaError::fs("Error reading current directory"))?;
+
+    let file_config = load_config(&cwd)?;
+
+    let env_roots = env::var(ENV_VAR).ok().map(|roots| split_roots(&roots));
+
+    // CLI flags override the config files. Roots are not replaced: the ones
+    // from `--root` come first, then `TADA_APP`, then the config ones.
+    let roots = [
+        args.roots.clone(),
+        env_roots.unwrap_or_default(),
+        file_config.roots.clone().unwrap_or_default(),
+    ]
+    .concat();
+
+    let config = Config {
+        roots: Some(roots),
+        packageManager: args.package_manager.clone(),
+        install: args.install(),
+        git: args.git(),
+        ..Default::default()
+    }
+    .or(file_config);
+
+    let mut root_specs: Vec<String> = Vec::new();
+    for root in config.roots.clone().unwrap_or_default() {
+        if !root_specs.contains(&root) {
+            root_specs.push(root);
+        }
+    }
+
+    let root_specs = match &args.command {
+        Some(Command::Validate { root: Some(root) }) => vec![root.clone()],
+        // The catalogs the app was generated from come first.
+        Some(Command::Upgrade { dir, .. }) => {
+            let generation = read_generation(dir.as_deref().unwrap_or(&cwd))?;
+
+            let mut specs = generation.roots;
+            for root in root_specs {
+                if !specs.contains(&root) {
+                    specs.push(root);
+                }
+            }
+            specs
+        }
+        _ => root_specs,
+    };
+
+    if root_specs.is_empty() {
+        return Err(TadaError::Catalog(format!(
+            "No catalog found, set the {} environment variable or `roots` in {}",
+            ENV_VAR, CONFIG_FILE
+        )));
     }
 
-    let cwd: PathBuf;
-    if let Ok(path) = env::current_dir() {
-        cwd = path;
-    } else {
-        let _ = outro_cancel("Error reading current directory");
-        std::process::exit(1);
+    let tada_app_roots = fetch_roots(&root_specs, &args)?;
+
+    match &args.command {
+        Some(Command::Validate { .. }) => {
+            return validate_catalogs(&tada_app_roots);
+        }
+        Some(Command::Test {
+            templates,
+            addons,
+            max_addons,
+            install,
+            scripts,
+            keep,
+        }) => {
+            return test_catalogs(
+                &tada_app_roots,
+                &TestOptions {
+                    templates: templates.clone(),
+                    addons: addons.clone(),
+                    max_addons: *max_addons,
+                    install: *install,
+                    scripts: scripts.clone(),
+                    keep: *keep,
+                    package_manager: config.package_manager().to_string(),
+                },
+            );
+        }
+        Some(Command::Snapshot {
+            update, scenarios, ..
+        }) => {
+            return snapshot_scenarios(
+                &tada_app_roots,
+                &SnapshotOptions {
+                    scenarios: scenarios.clone(),
+                    update: *update,
+                    registry: args.registry.clone(),
+                    registry_timeout: Duration::from_secs(args.registry_timeout),
+                    dist_tag: args.dist_tag.clone(),
+                },
+            );
+        }
+        Some(Command::Dev {
+            template,
+            addons,
+            script,
+            dir,
+        }) => {
+            return dev_addons(
+                &tada_app_roots,
+                &DevOptions {
+                    template: template.clone(),
+                    addons: addons.clone(),
+                    script: script.clone(),
+                    dir: dir.clone(),
+                    package_manager: config.package_manager().to_string(),
+                },
+            );
+        }
+        Some(Command::Upgrade { dir, dry_run }) => {
+            return upgrade_app(
+                &tada_app_roots,
+                &UpgradeOptions {
+                    dir: dir.clone().unwrap_or_else(|| cwd.clone()),
+                    dry_run: *dry_run,
+                    // Checked by clap.
+                    policy: ConflictPolicy::parse(
+                        &args.on_conflict,
+                        prompt_resolve_upgrade_conflict,
+                    )
+                    .unwrap_or(ConflictPolicy::Mode),
+                },
+            );
+        }
+        None => {}
     }
 
     let mut app_name = String::new();
-    prompt_app_path(&mut app_name);
+    prompt_app_path(&mut app_name)?;
+
+    let mut new_app_path = RelativePath::new(app_dir(&app_name)).to_logical_path(&cwd);
+
+    let workspace = new_app_path.parent().and_then(Workspace::find);
+
+    if let Some(workspace) = &workspace {
+        // An app generated right at the workspace root goes next to the
+        // other apps instead, when the workspace has a place for them.
+        if new_app_path.parent() == Some(workspace.root.as_path())
+            && !workspace.contains(&new_app_path)
+        {
+            if let Some(apps_dir) = workspace.apps_dir() {
+                if let Some(name) = new_app_path.file_name() {
+                    new_app_path = apps_dir.join(name);
+                }
+            }
+        }
+
+        let _ = log::info(format!(
+            "Generating into the workspace at {}",
+            workspace.root.display()
+        ));
+    }
 
-    let tada_templates_path = Path::new(&tada_app_path.clone()).join("templates");
+    let empty_target = prepare_target(&new_app_path, args.force)?;
 
-    let mut templates: BTreeMap<String, OsString> = BTreeMap::new();
-    get_templates(tada_templates_path.as_os_str(), &mut templates);
+    let project_name = match &args.package_name {
+        Some(name) => name.clone(),
+        None => {
+            let dir_name = new_app_path.file_name().and_then(|x| x.to_str());
+
+            match package_name(&app_name, dir_name) {
+                Some(name) => name,
+                None => prompt_package_name()?,
+            }
+        }
+    };
+
+    let mut shadowed: Vec<Shadowed> = Vec::new();
+
+    let mut templates: BTreeMap<String, Details> = BTreeMap::new();
+    for root in &tada_app_roots {
+        get_templates(root.as_os_str(), &mut templates, &mut shadowed)?;
+    }
 
     if templates.is_empty() {
-        let _ = outro_cancel("No templates found");
-        std::process::exit(1);
+        return Err(TadaError::Catalog("No templates found".to_string()));
     }
 
+    report_shadowed(&mut shadowed);
+
+    let mut presets: BTreeMap<String, Details> = BTreeMap::new();
+    for root in &tada_app_roots {
+        get_presets(root.as_os_str(), &mut presets, &mut shadowed)?;
+    }
+
+    report_shadowed(&mut shadowed);
+
+    let mut valid_presets: BTreeMap<String, (Details, PresetJson)> = BTreeMap::new();
+    for (name, details) in presets {
+        let preset = parse_json_file::<PresetJson>(details.path.as_os_str())
+            .map_err(TadaError::from)
+            .and_then(|preset| {
+                validate_preset(
+                    Path::new(&details.path),
+                    &preset,
+                    &templates,
+                    &tada_app_roots,
+                )?;
+                Ok(preset)
+            });
+
+        match preset {
+            Ok(preset) => {
+                valid_presets.insert(name, (details, preset));
+            }
+            Err(e) if args.preset.as_ref() == Some(&name) => return Err(e),
+            Err(e) => {
+                let _ = log::warning(format!("Skipping preset `{}`: {}", name, e));
+            }
+        }
+    }
+
+    let selected_preset: Option<PresetJson> = match &args.preset {
+        Some(name) => match valid_presets.remove(name) {
+            Some((_, preset)) => Some(preset),
+            None => {
+                return Err(TadaError::Catalog(format!("Preset `{}` not found", name)));
+            }
+        },
+        None if !valid_presets.is_empty() => prompt_select_preset(&valid_presets)?
+            .and_then(|name| valid_presets.remove(&name))
+            .map(|(_, preset)| preset),
+        None => None,
+    };
+
     let mut selected_template: Details = Details {
         name: "".to_string(),
         path: OsString::new(),
+        root: OsString::new(),
     };
-    prompt_select_template(templates, &mut selected_template);
 
-    let tada_addons_path = Path::new(&tada_app_path.clone()).join("addons");
+    // CLI flags override the answers pinned by the preset, which override
+    // the config files.
+    let config = match &selected_preset {
+        Some(preset) => {
+            // Checked by `validate_preset`.
+            if let Some(template) = templates.get(&preset.template) {
+                selected_template = template.clone();
+            }
+
+            Config {
+                packageManager: args.package_manager.clone(),
+                install: args.install(),
+                git: args.git(),
+                ..Default::default()
+            }
+            .or(Config {
+                packageManager: preset.packageManager.clone(),
+                install: preset.install,
+                ..Default::default()
+            })
+            .or(config)
+        }
+        None => {
+            let templates = templates
+                .into_iter()
+                .filter_map(|(name, details)| match read_template_json(&details.path) {
+                    Ok(meta) => Some((name, (details, meta))),
+                    Err(diagnostic) => {
+                        let _ = log::warning(format!("Skipping template, invalid {}", diagnostic));
+                        None
+                    }
+                })
+                .collect::<BTreeMap<_, _>>();
+
+            if templates.values().all(|(_, meta)| meta.hidden) {
+                return Err(TadaError::Catalog("No templates found".to_string()));
+            }
+
+            prompt_select_template(templates, &mut selected_template)?;
 
-    let mut addons: BTreeMap<String, OsString> = BTreeMap::new();
-    get_filtered_addons(
-        tada_addons_path.as_os_str(),
-        selected_template.name.clone(),
-        &mut addons,
-    );
+            config
+        }
+    };
 
-    let mut selected_addons: Vec<Details> = Vec::new();
-    if !addons.is_empty() {
-        prompt_select_addons(addons, &mut selected_addons);
+    let mut addons: BTreeMap<String, Details> = BTreeMap::new();
+    let mut invalid_addons: Vec<JsonDiagnostic> = Vec::new();
+    for root in &tada_app_roots {
+        get_filtered_addons(
+            root.as_os_str(),
+            selected_template.name.clone(),
+            &mut addons,
+            &mut shadowed,
+            &mut invalid_addons,
+        )?;
     }
 
-    let should_install_deps = prompt_install_deps();
-
-    let new_app_path = RelativePath::new(&app_name).to_logical_path(cwd);
+    for diagnostic in &invalid_addons {
+        let _ = log::warning(format!("Skipping addon, invalid {}", diagnostic));
+    }
 
-    if new_app_path.exists() {
-        if let Some(parent) = new_app_path.parent() {
-            let result = std::fs::create_dir_all(parent);
+    report_shadowed(&mut shadowed);
 
-            if let Err(e) = result {
-                let _ = outro_cancel(format!("Error creating directory: {:?}", e));
-                std::process::exit(1);
+    let mut selected_addons: Vec<Details> = Vec::new();
+    if !args.addons.is_empty() {
+        for addon_name in &args.addons {
+            match addons.get(addon_name) {
+                Some(addon) => selected_addons.push(addon.clone()),
+                None => {
+                    return Err(TadaError::Catalog(format!(
+                        "Addon `{}` is not available for `{}`",
+                        addon_name, selected_template.name
+                    )));
+                }
             }
         }
-    } else {
-        let result = std::fs::create_dir_all(&new_app_path);
-
-        if let Err(e) = result {
-            let _ = outro_cancel(format!("Error creating directory: {:?}", e));
-            std::process::exit(1);
+    } else if let Some(preset) = &selected_preset {
+        selected_addons = preset
+            .addons
+            .iter()
+            .filter_map(|x| addons.get(x).cloned())
+            .collect();
+    } else if !addons.is_empty() {
+        let default_addons = config
+            .default_addons(&selected_template.name)
+            .into_iter()
+            .filter(|x| addons.contains_key(x))
+            .collect::<Vec<_>>();
+
+        if config.prompt_addons() {
+            let addons = addons
+                .into_iter()
+                .map(|(name, details)| {
+                    let addon_tada_json_path = Path::new(&details.path).join(TADA_JSON);
+                    let meta: TadaJson = read_json_file(addon_tada_json_path.as_os_str())?;
+                    Ok((name, (details, meta)))
+                })
+                .collect::<Result<BTreeMap<_, _>>>()?;
+
+            prompt_select_addons(addons, default_addons, &mut selected_addons)?;
+        } else {
+            selected_addons = default_addons
+                .iter()
+                .filter_map(|x| addons.get(x).cloned())
+                .collect();
         }
     }
 
-    let items_to_ignore = IGNORE.map(|x| x.to_string()).to_vec();
-    let items_in_template = get_items_in_template(&selected_template.path, items_to_ignore);
-    let os_items_in_template = items_in_template
+    let install_default = config.install.unwrap_or(true);
+    let pinned_install = args
+        .install()
+        .or(selected_preset.as_ref().and_then(|x| x.install));
+    let should_install_deps = match pinned_install {
+        Some(install) => install,
+        None if config.prompt_install() => prompt_install_deps(install_default)?,
+        None => install_default,
+    };
+
+    let git_default = config.git.unwrap_or(true);
+    let should_init_git = match args.git() {
+        Some(git) => git,
+        None if config.prompt_git() => prompt_git_init(git_default)?,
+        None => git_default,
+    };
+
+    let mut hooks = match args.no_hooks {
+        true => Vec::new(),
+        false => collect_hooks(&selected_template, &selected_addons)?,
+    };
+
+    let untrusted_hooks = hooks
         .iter()
-        .map(|x| Path::new(x).as_os_str())
+        .filter(|x| !x.trusted)
+        .cloned()
         .collect::<Vec<_>>();
+    if !untrusted_hooks.is_empty() && !args.trust_hooks && !prompt_run_hooks(&untrusted_hooks)? {
+        let _ = log::remark(format!(
+            "Skipping {} hook(s) from remote catalogs",
+            untrusted_hooks.len()
+        ));
+        hooks.retain(|x| x.trusted);
+    }
 
-    let copy_template_spinner = start_spinner("Copying template...");
+    if empty_target {
+        empty_dir(&new_app_path)
+            .map_err(TadaError::fs(format!("Error emptying {:?}", new_app_path)))?;
+    }
 
-    let template_copied = copy_items(
-        &os_items_in_template,
-        new_app_path.as_os_str(),
-        &CopyOptions::new(),
-    );
+    std::fs::create_dir_all(&new_app_path).map_err(TadaError::fs(format!(
+        "Error creating directory: {:?}",
+        new_app_path
+    )))?;
 
-    if let Err(e) = template_copied {
-        let _ = outro_cancel(format!("Error copying template: {:?}", e));
-        std::process::exit(1);
-    }
+    let mut repository = match should_init_git {
+        true => init_repository(&new_app_path),
+        false => None,
+    };
+    // Every step is committed separately, then the rest goes in a last
+    // commit.
+    let commit_steps = args.commit_per_addon && repository.is_some();
+
+    let ignore = ignore_patterns(workspace.is_some());
+
+    let copy_template_spinner = start_spinner("Copying template...");
+
+    copy_template(&selected_template, &new_app_path, &ignore)
+        .inspect_err(|_| copy_template_spinner.error("Unable to copy template"))?;
 
     copy_template_spinner.stop("Template ready!");
 
-    let project_package_json_path = Path::new(&new_app_path).join("package.json");
-    let mut project_package_json: PackageJson =
-        read_json_file(project_package_json_path.as_os_str());
+    let mut manifest = Manifest::read(&new_app_path)?;
 
-    let mut dependencies: BTreeMap<String, String> = BTreeMap::new();
-    for (key, value) in project_package_json.dependencies.unwrap() {
-        dependencies.insert(key, value);
+    if commit_steps {
+        manifest.write(&new_app_path, project_name.clone())?;
+        commit(
+            &mut repository,
+            &format!("Add the {} template", selected_template.name),
+        );
     }
 
-    let mut dev_dependencies: BTreeMap<String, String> = BTreeMap::new();
-    for (key, value) in project_package_json.devDependencies.unwrap() {
-        dev_dependencies.insert(key, value);
-    }
+    // Checked by clap.
+    let conflict_policy = ConflictPolicy::parse(&args.on_conflict, prompt_resolve_conflict)
+        .unwrap_or(ConflictPolicy::Mode);
 
-    let mut scripts: BTreeMap<String, String> = BTreeMap::new();
-    if let Some(scripts_map) = project_package_json.scripts {
-        for (key, value) in scripts_map {
-            scripts.insert(key, value);
+    if !selected_addons.is_empty() {
+        // Conflicts are asked about, which a spinner would hide.
+        let copy_addons_spinner = (!matches!(conflict_policy, ConflictPolicy::Ask(_)))
+            .then(|| start_spinner("Copying addons..."));
+
+        for addon in &selected_addons {
+            apply_addon(addon, &new_app_path, &mut manifest, conflict_policy).inspect_err(
+                |_| {
+                    if let Some(spinner) = &copy_addons_spinner {
+                        spinner.error("Unable to copy addons");
+                    }
+                },
+            )?;
+
+            if commit_steps {
+                manifest.write(&new_app_path, project_name.clone())?;
+                commit(&mut repository, &format!("Add the {} addon", addon.name));
+            }
+        }
+
+        match copy_addons_spinner {
+            Some(spinner) => spinner.stop("Addons ready!"),
+            None => {
+                let _ = log::success("Addons ready!");
+            }
         }
     }
 
-    if !selected_addons.is_empty() {
-        let copy_addons_spinner = start_spinner("Copying addons...");
+    let moved_dependencies = manifest.dedupe();
 
-        for addon in &selected_addons {
-            let addon_path = Path::new(&addon.path);
+    for moved in &moved_dependencies {
+        let _ = log::info(format!("Deduplicated {}", moved));
+    }
 
-            let addon_tada_json_path = Path::new(addon_path).join("tada.json");
-            let addon_package_json_path = Path::new(addon_path).join("package.json");
+    let mut report = RegistryReport::default();
 
-            let addon_tada_json: TadaJson = read_json_file(addon_tada_json_path.as_os_str());
-            let addon_package_json: PackageJson =
-                read_json_file(addon_package_json_path.as_os_str());
+    if args.latest {
+        let resolve_spinner = start_spinner("Resolving latest versions...");
 
-            if let Some(dependencies_map) = addon_package_json.dependencies {
-                for (key, value) in dependencies_map {
-                    dependencies.insert(key, value);
-                }
-            }
+        let registry = Registry::new(&args.registry, Duration::from_secs(args.registry_timeout));
 
-            if let Some(dev_dependencies_map) = addon_package_json.devDependencies {
-                for (key, value) in dev_dependencies_map {
-                    dev_dependencies.insert(key, value);
-                }
-            }
+        for dependencies in [&mut manifest.dependencies, &mut manifest.dev_dependencies] {
+            registry.resolve(
+                dependencies,
+                &args.dist_tag,
+                args.latest_pinned,
+                &mut report,
+            );
+        }
 
-            if let Some(scripts_map) = addon_package_json.scripts {
-                for (key, value) in scripts_map {
-                    scripts.insert(key, value);
-                }
-            }
+        resolve_spinner.stop(format!("Resolved {} versions", report.resolved.len()));
 
-            for addon_entry in &addon_tada_json.entries {
-                let addon_entry_source =
-                    Path::new(&addon_path).join(OsString::from(&addon_entry.input).as_os_str());
-                let addon_entry_destination =
-                    new_app_path.join(OsString::from(&addon_entry.output).as_os_str());
-
-                let addon_entry_os_source = addon_entry_source.as_os_str();
-                let addon_entry_os_destination = addon_entry_destination.as_os_str();
-
-                let addon_copied = copy_addon_items(
-                    &[addon_entry_os_source],
-                    addon_entry_os_destination,
-                    &addon_entry.mode,
-                );
-
-                if let Err(_) = addon_copied {
-                    let _ = outro_cancel(format!(
-                        "Error copying addon: {:?}, from: {:?}, to: {:?}",
-                        addon.name, addon_entry_os_source, addon_entry_os_destination
-                    ));
-                    std::process::exit(1);
-                }
-            }
+        for resolved in &report.resolved {
+            let _ = log::info(format!(
+                "{}: {} -> {}",
+                resolved.name, resolved.declared, resolved.resolved
+            ));
         }
 
-        copy_addons_spinner.stop("Addons ready!");
+        if report.offline {
+            let _ = log::warning(format!(
+                "Unable to reach {}, keeping the declared ranges",
+                args.registry
+            ));
+        }
+
+        for (name, reason) in &report.kept {
+            let _ = log::remark(format!("{}: kept declared range ({})", name, reason));
+        }
     }
 
-    project_package_json.name = match new_app_path.file_name() {
-        Some(name) => name.to_str().unwrap().to_string(),
-        None => selected_template.name.clone(),
-    };
-    project_package_json.scripts = Some(scripts);
-    project_package_json.dependencies = Some(dependencies);
-    project_package_json.devDependencies = Some(dev_dependencies);
-
-    let new_package_json_string = serde_json::to_string_pretty(&project_package_json)
-        .with_context(|| "Error serializing package.json")
-        .unwrap();
-
-    let mut new_package_json_file = File::create(project_package_json_path)
-        .with_context(|| "Error creating package.json file")
-        .unwrap();
-
-    let result = std::io::Write::write(
-        &mut new_package_json_file,
-        new_package_json_string.as_bytes(),
-    );
-
-    if let Err(e) = result {
-        let _ = outro_cancel(format!("Error writing `package.json` file: {:?}", e));
-        std::process::exit(1);
+    manifest.write(&new_app_path, project_name.clone())?;
+
+    // Before the hooks and the install, which are not replayed on upgrade.
+    record_generation(
+        &new_app_path,
+        &selected_template,
+        &selected_addons,
+        &GenerationJson {
+            template: selected_template.name.clone(),
+            addons: selected_addons.iter().map(|x| x.name.clone()).collect(),
+            packageName: project_name,
+            skipLockfiles: workspace.is_some(),
+            roots: root_specs.iter().map(|x| absolute_root(x)).collect(),
+            latestVersions: report
+                .resolved
+                .into_iter()
+                .map(|x| {
+                    let version = LatestVersion {
+                        declared: x.declared,
+                        resolved: x.resolved,
+                    };
+                    (x.name, version)
+                })
+                .collect(),
+        },
+    )?;
+
+    if let Some(workspace) = &workspace {
+        if !workspace.contains(&new_app_path) {
+            let registered = workspace
+                .register(&new_app_path)
+                .map_err(TadaError::fs("Error registering app in the workspace"))?;
+            let relative = workspace.relative(&new_app_path).unwrap_or_default();
+
+            let _ = match registered {
+                true => log::info(format!("Registered {} in the workspace packages", relative)),
+                false => log::warning(format!(
+                    "Unable to find the workspace packages, add {} to them by hand",
+                    relative
+                )),
+            };
+        }
     }
 
+    run_hooks(&hooks, Stage::PostCopy, &new_app_path)?;
+
     if should_install_deps {
         let install_deps_spinner = start_spinner("Installing dependencies...");
 
-        if install_dependencies("pnpm", new_app_path) {
-            install_deps_spinner.stop("Dependencies installed!");
-        }
+        // `--package-manager` wins over the one the workspace is set up with.
+        let (pm, install_path) = match (&workspace, &args.package_manager) {
+            (Some(workspace), Some(pm)) => {
+                if pm != workspace.package_manager() {
+                    let _ = log::warning(format!(
+                        "The workspace uses {}, installing with {} as requested",
+                        workspace.package_manager(),
+                        pm
+                    ));
+                }
+                (pm.as_str(), workspace.root.clone())
+            }
+            (Some(workspace), None) => (workspace.package_manager(), workspace.root.clone()),
+            (None, _) => (config.package_manager(), new_app_path.clone()),
+        };
+
+        install_dependencies(pm, install_path)
+            .inspect_err(|_| install_deps_spinner.error("Unable to install dependencies"))?;
+
+        install_deps_spinner.stop("Dependencies installed!");
+
+        run_hooks(&hooks, Stage::PostInstall, &new_app_path)?;
+    } else if hooks.iter().any(|x| x.stage == Stage::PostInstall) {
+        let _ = log::remark("Skipping the post-install hooks, dependencies are not installed");
+    }
+
+    match commit_steps {
+        true => commit(&mut repository, "Resolve dependencies"),
+        false => commit(&mut repository, "Initial commit"),
     }
 
     let _ = outro("ENJOY! 🎉");
+
+    Ok(())
 }
@@ -1,95 +1,264 @@
 #![deny(clippy::all)]
 
 use std::collections::BTreeMap;
-use std::ffi::OsString;
+use std::path::Path;
 
-use anyhow::Context;
-use cliclack::{confirm, input, multiselect, select};
+use cliclack::{confirm, input, multiselect, note, select};
 
-use super::utils::fs::Details;
+use super::error::{Result, TadaError};
+use super::utils::conflict::Resolution;
+use super::utils::fs::{Details, PresetJson, TadaJson, TemplateJson};
+use super::utils::hooks::Hook;
+use super::utils::name::{validate_app_path, validate_package_name};
+use super::utils::target::ExistingTarget;
+
+/// Above this many addons, the multiselect can be filtered by typing.
+const ADDONS_FILTER_THRESHOLD: usize = 8;
+
+pub fn prompt_select_preset(
+    options: &BTreeMap<String, (Details, PresetJson)>,
+) -> Result<Option<String>> {
+    let mut options_names = options
+        .iter()
+        .map(|(name, (_, preset))| {
+            let hint = match &preset.description {
+                Some(description) => description.clone(),
+                None => [preset.template.clone()]
+                    .into_iter()
+                    .chain(preset.addons.iter().cloned())
+                    .collect::<Vec<_>>()
+                    .join(" + "),
+            };
+
+            (Some(name.clone()), name.clone(), hint)
+        })
+        .collect::<Vec<_>>();
+    options_names.push((
+        None,
+        "No preset".to_string(),
+        "choose a template".to_string(),
+    ));
+
+    select("Start from a preset?")
+        .items(options_names.as_slice())
+        .interact()
+        .map_err(TadaError::prompt)
+}
 
 pub fn prompt_select_template(
-    options: BTreeMap<String, OsString>,
+    options: BTreeMap<String, (Details, TemplateJson)>,
     selected_template: &mut Details,
-) {
-    let options_names = options
-        .keys()
-        .map(|x| (x.to_string(), x.clone(), ""))
+) -> Result<()> {
+    let mut visible_options = options
+        .values()
+        .filter(|(_, meta)| !meta.hidden)
+        .collect::<Vec<_>>();
+    visible_options.sort_by_key(|(details, meta)| (meta.order.unwrap_or(i64::MAX), &details.name));
+
+    let options_names = visible_options
+        .iter()
+        .map(|(details, meta)| {
+            let mut label = meta.name.clone().unwrap_or(details.name.clone());
+            if meta.experimental {
+                label.push_str(" (experimental)");
+            }
+
+            let mut hint: Vec<String> = Vec::new();
+            if let Some(description) = &meta.description {
+                hint.push(description.clone());
+            }
+            if !meta.tags.is_empty() {
+                hint.push(format!("[{}]", meta.tags.join(", ")));
+            }
+            hint.push(details.root.to_string_lossy().to_string());
+
+            (details.name.clone(), label, hint.join(" · "))
+        })
         .collect::<Vec<_>>();
 
-    let template_selected = select("Choose a template")
+    let selected = select("Choose a template")
         .items(options_names.as_slice())
         .interact()
-        .with_context(|| "No template selected, exiting");
-
-    match template_selected {
-        Ok(selected) => {
-            selected_template.name = selected.clone();
-            selected_template.path = options
-                .get(&selected)
-                .with_context(|| "Unable to get template")
-                .unwrap()
-                .to_os_string();
-        }
-        Err(e) => {
-            println!("\n\n{:?}", e);
-            std::process::exit(1);
-        }
+        .map_err(TadaError::prompt)?;
+
+    if let Some((details, _)) = options.get(&selected) {
+        *selected_template = details.clone();
     }
+
+    Ok(())
 }
 
-pub fn prompt_select_addons(options: BTreeMap<String, OsString>, addons: &mut Vec<Details>) {
-    let options_names = options
-        .keys()
-        .map(|x| (x.to_string(), x.clone(), ""))
+pub fn prompt_select_addons(
+    options: BTreeMap<String, (Details, TadaJson)>,
+    initial_values: Vec<String>,
+    addons: &mut Vec<Details>,
+) -> Result<()> {
+    // Addons are grouped by category, uncategorized ones last. The category
+    // is part of the label so the filter matches it too.
+    let mut sorted_options = options.values().collect::<Vec<_>>();
+    sorted_options
+        .sort_by_key(|(details, meta)| (meta.category.is_none(), &meta.category, &details.name));
+
+    let options_names = sorted_options
+        .iter()
+        .map(|(details, meta)| {
+            let label = match &meta.category {
+                Some(category) => format!("{}: {}", category, details.name),
+                None => details.name.clone(),
+            };
+
+            let hint = match &meta.description {
+                Some(description) => {
+                    format!("{} · {}", description, details.root.to_string_lossy())
+                }
+                None => details.root.to_string_lossy().to_string(),
+            };
+
+            (details.name.clone(), label, hint)
+        })
         .collect::<Vec<_>>();
 
-    let addons_selected = multiselect("Choose the addons you want to use:")
+    let mut prompt = multiselect("Choose the addons you want to use:")
         .items(options_names.as_slice())
-        .required(false)
-        .interact()
-        .with_context(|| "No addons selected, exiting");
-
-    match addons_selected {
-        Ok(selected) => {
-            for addon_name in selected {
-                let path_to_selected_addon = options.get(&addon_name).unwrap();
-                addons.push(Details {
-                    name: addon_name.to_string(),
-                    path: path_to_selected_addon.clone(),
-                });
-            }
-        }
-        Err(e) => {
-            println!("\n{:?}", e);
-            std::process::exit(1);
+        .initial_values(initial_values)
+        .required(false);
+
+    if options_names.len() > ADDONS_FILTER_THRESHOLD {
+        prompt = prompt.filter_mode();
+    }
+
+    let selected = prompt.interact().map_err(TadaError::prompt)?;
+
+    for addon_name in selected {
+        if let Some((details, _)) = options.get(&addon_name) {
+            addons.push(details.clone());
         }
     }
+
+    Ok(())
 }
 
-pub fn prompt_app_path(app_name: &mut String) {
-    let name_provided = input("What is the name of your project?")
+pub fn prompt_app_path(app_name: &mut String) -> Result<()> {
+    *app_name = input("What is the name of your project?")
         .placeholder("./my-project")
+        .validate(|input: &String| validate_app_path(input))
         .interact()
-        .with_context(|| "No project name provided, exiting");
+        .map_err(TadaError::prompt)?;
 
-    match name_provided {
-        Ok(name) => {
-            *app_name = name;
-        }
-        Err(e) => {
-            println!("\n\n{:?}", e);
-            std::process::exit(1);
-        }
-    }
+    Ok(())
+}
+
+/// Asked when the package name cannot be derived from the directory name.
+pub fn prompt_package_name() -> Result<String> {
+    input("What is the package name?")
+        .placeholder("my-project")
+        .validate(|input: &String| validate_package_name(input))
+        .interact()
+        .map_err(TadaError::prompt)
 }
 
-pub fn prompt_install_deps() -> bool {
-    let value = confirm("Should we install the dependencies?")
-        .initial_value(true)
+pub fn prompt_install_deps(initial_value: bool) -> Result<bool> {
+    confirm("Should we install the dependencies?")
+        .initial_value(initial_value)
         .interact()
-        .with_context(|| "No confirmation provided, exiting")
-        .unwrap();
+        .map_err(TadaError::prompt)
+}
+
+pub fn prompt_git_init(initial_value: bool) -> Result<bool> {
+    confirm("Should we initialize a git repository?")
+        .initial_value(initial_value)
+        .interact()
+        .map_err(TadaError::prompt)
+}
+
+/// Hooks from fetched catalogs run arbitrary commands, so they are shown
+/// before asking.
+pub fn prompt_run_hooks(hooks: &[Hook]) -> Result<bool> {
+    let commands = hooks
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let _ = note("Hooks from remote catalogs", commands);
+
+    confirm("Should we run these commands?")
+        .initial_value(false)
+        .interact()
+        .map_err(TadaError::prompt)
+}
+
+/// Binary files, without `diff`, can only be kept or replaced.
+pub fn prompt_resolve_conflict(path: &Path, diff: Option<&str>) -> Result<Resolution> {
+    let _ = note(
+        format!("{} already exists", path.display()),
+        diff.unwrap_or("Binary files differ"),
+    );
 
-    value
+    let mut prompt = select("What should we do with it?")
+        .item(Resolution::Keep, "Keep mine", "")
+        .item(Resolution::Take, "Take the addon version", "");
+
+    if diff.is_some() {
+        prompt = prompt
+            .item(Resolution::Markers, "Merge with conflict markers", "")
+            .item(
+                Resolution::Edit,
+                "Merge, then open in the editor",
+                "$VISUAL or $EDITOR",
+            );
+    }
+
+    prompt.interact().map_err(TadaError::prompt)
+}
+
+/// Same choices as `prompt_resolve_conflict`, for a file that both the app
+/// and the catalog changed since the last generation.
+pub fn prompt_resolve_upgrade_conflict(path: &Path, diff: Option<&str>) -> Result<Resolution> {
+    let _ = note(
+        format!("{} changed in the app and in the catalog", path.display()),
+        diff.unwrap_or("Binary files differ"),
+    );
+
+    let mut prompt = select("What should we do with it?")
+        .item(Resolution::Keep, "Keep mine", "")
+        .item(Resolution::Take, "Take the catalog version", "");
+
+    if diff.is_some() {
+        prompt = prompt
+            .item(Resolution::Markers, "Merge with conflict markers", "")
+            .item(
+                Resolution::Edit,
+                "Merge, then open in the editor",
+                "$VISUAL or $EDITOR",
+            );
+    }
+
+    prompt.interact().map_err(TadaError::prompt)
+}
+
+pub fn prompt_existing_target(path: &Path) -> Result<ExistingTarget> {
+    select(format!(
+        "{} is not empty. What should we do?",
+        path.display()
+    ))
+    .item(ExistingTarget::Cancel, "Cancel", "")
+    .item(ExistingTarget::Empty, "Remove its files first", "")
+    .item(ExistingTarget::Merge, "Generate over its files", "")
+    .interact()
+    .map_err(TadaError::prompt)
+}
+
+pub fn prompt_empty_target(path: &Path) -> Result<bool> {
+    let message = match path.join(".git").exists() {
+        true => format!(
+            "Remove everything in {}, including its .git repository and history?",
+            path.display()
+        ),
+        false => format!("Remove everything in {}?", path.display()),
+    };
+
+    confirm(message)
+        .initial_value(false)
+        .interact()
+        .map_err(TadaError::prompt)
 }
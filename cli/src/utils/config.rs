@@ -0,0 +1,214 @@
+#![deny(clippy::all)]
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::diagnostic::{parse_json_file, JsonDiagnostic};
+use super::source::Source;
+use crate::error::{Result, TadaError};
+
+pub const CONFIG_FILE: &str = "tada.config.json";
+pub const PACKAGE_MANAGERS: [&str; 3] = ["pnpm", "yarn", "npm"];
+
+/// Whether a prompt is shown; when it is not, its default answer is used.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct PromptsConfig {
+    pub install: Option<bool>,
+    pub addons: Option<bool>,
+    pub git: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[allow(non_snake_case)]
+pub struct Config {
+    /// Catalog roots: local paths, relative to the config file they are
+    /// declared in, git repositories or archives.
+    pub roots: Option<Vec<String>>,
+    pub packageManager: Option<String>,
+    pub install: Option<bool>,
+    /// Initialize a git repository with the new app.
+    pub git: Option<bool>,
+    /// Addons selected by default, per template name.
+    pub addons: Option<BTreeMap<String, Vec<String>>>,
+    pub prompts: Option<PromptsConfig>,
+}
+
+impl Config {
+    /// Fills every unset value of `self`, the closer config, with the one
+    /// from `other`. Roots set in `self` replace the ones of `other`.
+    pub fn or(self, other: Config) -> Config {
+        let prompts = match (self.prompts, other.prompts) {
+            (Some(prompts), Some(other_prompts)) => Some(PromptsConfig {
+                install: prompts.install.or(other_prompts.install),
+                addons: prompts.addons.or(other_prompts.addons),
+                git: prompts.git.or(other_prompts.git),
+            }),
+            (prompts, other_prompts) => prompts.or(other_prompts),
+        };
+
+        Config {
+            roots: self.roots.or(other.roots),
+            packageManager: self.packageManager.or(other.packageManager),
+            install: self.install.or(other.install),
+            git: self.git.or(other.git),
+            addons: self.addons.or(other.addons),
+            prompts,
+        }
+    }
+
+    pub fn package_manager(&self) -> &str {
+        self.packageManager.as_deref().unwrap_or("pnpm")
+    }
+
+    pub fn prompt_install(&self) -> bool {
+        self.prompts
+            .as_ref()
+            .and_then(|x| x.install)
+            .unwrap_or(true)
+    }
+
+    pub fn prompt_addons(&self) -> bool {
+        self.prompts.as_ref().and_then(|x| x.addons).unwrap_or(true)
+    }
+
+    pub fn prompt_git(&self) -> bool {
+        self.prompts.as_ref().and_then(|x| x.git).unwrap_or(true)
+    }
+
+    pub fn default_addons(&self, template_name: &str) -> Vec<String> {
+        self.addons
+            .as_ref()
+            .and_then(|x| x.get(template_name))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// User-level config, under `$XDG_CONFIG_HOME/tada-app` (`~/.config` when
+/// unset, `%APPDATA%` on Windows).
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            if cfg!(windows) {
+                env::var_os("APPDATA").map(PathBuf::from)
+            } else {
+                env::var_os("HOME").map(|home| Path::new(&home).join(".config"))
+            }
+        })?;
+
+    Some(config_home.join("tada-app").join(CONFIG_FILE))
+}
+
+/// Repo-level config, the closest `tada.config.json` above `cwd`.
+pub fn project_config_path(cwd: &Path) -> Option<PathBuf> {
+    cwd.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+pub fn read_config(path: &Path) -> Result<Config> {
+    let mut config: Config = parse_json_file(path.as_os_str())?;
+
+    if let Some(package_manager) = &config.packageManager {
+        if !PACKAGE_MANAGERS.contains(&package_manager.as_str()) {
+            return Err(TadaError::Manifest(JsonDiagnostic {
+                path: path.to_path_buf(),
+                location: None,
+                message: format!(
+                    "invalid package manager `{}`, expected one of: {}",
+                    package_manager,
+                    PACKAGE_MANAGERS.join(", ")
+                ),
+                suggestion: None,
+            }));
+        }
+    }
+
+    if let (Some(roots), Some(dir)) = (&mut config.roots, path.parent()) {
+        for root in roots.iter_mut() {
+            match Source::parse(root) {
+                Source::Local(local) => {
+                    *root = dir.join(local).to_string_lossy().to_string();
+                }
+                Source::Archive { location } if !location.contains("://") => {
+                    *root = dir.join(location).to_string_lossy().to_string();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(config)
+}
+
+/// Project config overrides user config, roots included.
+pub fn load_config(cwd: &Path) -> Result<Config> {
+    let mut config = Config::default();
+
+    if let Some(path) = project_config_path(cwd) {
+        config = config.or(read_config(&path)?);
+    }
+
+    if let Some(path) = user_config_path().filter(|x| x.is_file()) {
+        config = config.or(read_config(&path)?);
+    }
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roots(roots: &[&str]) -> Option<Vec<String>> {
+        Some(roots.iter().map(|x| x.to_string()).collect())
+    }
+
+    #[test]
+    fn closer_config_wins() {
+        let project = Config {
+            roots: roots(&["/project/catalog"]),
+            install: Some(false),
+            prompts: Some(PromptsConfig {
+                git: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let user = Config {
+            roots: roots(&["/home/catalog"]),
+            packageManager: Some("yarn".to_string()),
+            install: Some(true),
+            prompts: Some(PromptsConfig {
+                install: Some(false),
+                git: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let config = project.or(user);
+
+        assert_eq!(config.roots, roots(&["/project/catalog"]));
+        assert_eq!(config.package_manager(), "yarn");
+        assert_eq!(config.install, Some(false));
+        assert!(!config.prompt_git());
+        assert!(!config.prompt_install());
+        assert!(config.prompt_addons());
+    }
+
+    #[test]
+    fn roots_fall_back_to_farther_configs() {
+        let config = Config::default().or(Config {
+            roots: roots(&["/home/catalog"]),
+            ..Default::default()
+        });
+
+        assert_eq!(config.roots, roots(&["/home/catalog"]));
+    }
+}
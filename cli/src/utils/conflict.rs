@@ -0,0 +1,291 @@
+#![deny(clippy::all)]
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use diffy::{create_patch, DiffOptions, Line};
+
+use crate::error::{Result, TadaError};
+use crate::utils::fs::TextStyle;
+
+pub const CONFLICT_POLICIES: [&str; 5] = ["mode", "ask", "keep", "take", "markers"];
+
+/// Asks for the resolution of the conflict on `path`. `diff` is `None` for
+/// binary files, which can only be kept or taken.
+pub type Resolver = fn(path: &Path, diff: Option<&str>) -> Result<Resolution>;
+
+/// What happens when an addon entry would replace an existing file, with
+/// the `overwrite`, `skip_if_exists` or default patch mode. Prepended and
+/// appended entries never conflict.
+#[derive(Debug, Clone, Copy)]
+pub enum ConflictPolicy {
+    /// The mode of the entry decides.
+    Mode,
+    /// The resolver picks a resolution for every file.
+    Ask(Resolver),
+    Keep,
+    Take,
+    Markers,
+}
+
+impl ConflictPolicy {
+    /// `ask` is the resolver of the `ask` policy, usually a prompt.
+    pub fn parse(policy: &str, ask: Resolver) -> Option<ConflictPolicy> {
+        match policy {
+            "mode" => Some(ConflictPolicy::Mode),
+            "ask" => Some(ConflictPolicy::Ask(ask)),
+            "keep" => Some(ConflictPolicy::Keep),
+            "take" => Some(ConflictPolicy::Take),
+            "markers" => Some(ConflictPolicy::Markers),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep the existing file.
+    Keep,
+    /// Replace it with the addon file.
+    Take,
+    /// Write both versions of every changed block between git-style
+    /// conflict markers.
+    Markers,
+    /// Write the markers, then open the file in `$VISUAL` or `$EDITOR`.
+    Edit,
+}
+
+/// Both versions, with git-style markers around the blocks that differ.
+fn with_markers(mine: &str, addon: &str) -> String {
+    let context_len = mine.lines().count().max(addon.lines().count());
+    let patch = DiffOptions::new()
+        .set_context_len(context_len)
+        .create_patch(mine, addon);
+
+    let mut merged = String::new();
+    let mut ours = String::new();
+    let mut theirs = String::new();
+
+    fn flush(merged: &mut String, ours: &mut String, theirs: &mut String) {
+        if ours.is_empty() && theirs.is_empty() {
+            return;
+        }
+
+        let parts = [
+            "<<<<<<< mine\n",
+            ours.as_str(),
+            "=======\n",
+            theirs.as_str(),
+            ">>>>>>> addon\n",
+        ];
+
+        for part in parts {
+            merged.push_str(part);
+            if !merged.ends_with('\n') {
+                merged.push('\n');
+            }
+        }
+
+        ours.clear();
+        theirs.clear();
+    }
+
+    for hunk in patch.hunks() {
+        for line in hunk.lines() {
+            match line {
+                Line::Context(line) => {
+                    flush(&mut merged, &mut ours, &mut theirs);
+                    merged.push_str(line);
+                }
+                Line::Delete(line) => ours.push_str(line),
+                Line::Insert(line) => theirs.push_str(line),
+            }
+        }
+    }
+    flush(&mut merged, &mut ours, &mut theirs);
+
+    merged
+}
+
+fn editor() -> String {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|x| !x.trim().is_empty())
+        .unwrap_or_else(|| match cfg!(windows) {
+            true => "notepad".to_string(),
+            false => "vi".to_string(),
+        })
+}
+
+/// The editor may come with arguments, e.g. `code --wait`.
+pub fn open_in_editor(path: &Path) -> Result<()> {
+    let editor = editor();
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or_default();
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(TadaError::fs(format!(
+            "Error opening {:?} in `{}`",
+            path, editor
+        )))?;
+
+    if !status.success() {
+        return Err(TadaError::Merge {
+            path: path.to_path_buf(),
+            message: format!("`{}` exited with {}", editor, status),
+        });
+    }
+
+    Ok(())
+}
+
+/// Resolves the conflict between the existing file `to` and the addon file
+/// `from`, with the resolver of `policy` when it is `Ask`. Text files are
+/// compared and merged without their line endings and BOM, then written
+/// in the style of `to`, so files that only differ in style do not
+/// conflict.
+pub fn resolve_conflict(from: &Path, to: &Path, policy: ConflictPolicy) -> Result<u64> {
+    let addon = fs::read(from).map_err(TadaError::fs(format!("Error reading {:?}", from)))?;
+    let mine = fs::read(to).map_err(TadaError::fs(format!("Error reading {:?}", to)))?;
+
+    if addon == mine {
+        return Ok(0);
+    }
+
+    // Markers only make sense for text.
+    let texts = match (String::from_utf8(mine), String::from_utf8(addon.clone())) {
+        (Ok(mine), Ok(addon))
+            if !content_inspector::inspect(mine.as_bytes()).is_binary()
+                && !content_inspector::inspect(addon.as_bytes()).is_binary() =>
+        {
+            let style = match mine.is_empty() {
+                true => TextStyle::detect(&addon),
+                false => TextStyle::detect(&mine),
+            };
+            Some((
+                style,
+                TextStyle::normalize(&mine),
+                TextStyle::normalize(&addon),
+            ))
+        }
+        _ => None,
+    };
+
+    if let Some((style, mine, addon)) = &texts {
+        if style.apply(mine) == style.apply(addon) {
+            return Ok(0);
+        }
+    }
+
+    let resolution = match policy {
+        // `Mode` is handled by `copy_addon_file`, it never gets here.
+        ConflictPolicy::Mode | ConflictPolicy::Keep => Resolution::Keep,
+        ConflictPolicy::Take => Resolution::Take,
+        ConflictPolicy::Markers => Resolution::Markers,
+        ConflictPolicy::Ask(ask) => {
+            let diff = texts
+                .as_ref()
+                .map(|(_, mine, addon)| create_patch(mine, addon).to_string());
+            ask(to, diff.as_deref())?
+        }
+    };
+
+    let content = match (resolution, &texts) {
+        (Resolution::Keep, _) => return Ok(0),
+        (Resolution::Take, Some((style, _, addon))) => style.apply(addon).into_bytes(),
+        (Resolution::Take, None) => addon,
+        (Resolution::Markers | Resolution::Edit, Some((style, mine, addon))) => {
+            style.apply(&with_markers(mine, addon)).into_bytes()
+        }
+        (_, None) => {
+            return Err(TadaError::Merge {
+                path: to.to_path_buf(),
+                message: "binary files cannot be merged with markers, keep or take one of them"
+                    .to_string(),
+            })
+        }
+    };
+
+    fs::write(to, &content).map_err(TadaError::fs(format!("Error writing {:?}", to)))?;
+
+    if resolution == Resolution::Edit {
+        open_in_editor(to)?;
+    }
+
+    Ok(content.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conflict(mine: &[u8], addon: &[u8], policy: ConflictPolicy) -> Result<Vec<u8>> {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("addon");
+        let to = dir.path().join("mine");
+        fs::write(&from, addon).unwrap();
+        fs::write(&to, mine).unwrap();
+
+        resolve_conflict(&from, &to, policy)?;
+
+        Ok(fs::read(&to).unwrap())
+    }
+
+    #[test]
+    fn markers_surround_the_changed_lines_in_the_existing_style() {
+        let merged = conflict(b"a\r\nb\r\nc\r\n", b"a\nB\nc\n", ConflictPolicy::Markers).unwrap();
+
+        assert_eq!(
+            String::from_utf8(merged).unwrap(),
+            "a\r\n<<<<<<< mine\r\nb\r\n=======\r\nB\r\n>>>>>>> addon\r\nc\r\n"
+        );
+    }
+
+    #[test]
+    fn take_keeps_the_existing_style() {
+        let taken = conflict("\u{feff}a\r\n".as_bytes(), b"b", ConflictPolicy::Take).unwrap();
+
+        assert_eq!(String::from_utf8(taken).unwrap(), "\u{feff}b\r\n");
+    }
+
+    #[test]
+    fn style_changes_are_not_conflicts() {
+        fn unreachable(_: &Path, _: Option<&str>) -> Result<Resolution> {
+            panic!("asked about a file that does not conflict");
+        }
+
+        let kept = conflict(b"a\r\nb", b"a\nb\n", ConflictPolicy::Ask(unreachable)).unwrap();
+
+        assert_eq!(kept, b"a\r\nb");
+    }
+
+    #[test]
+    fn the_resolver_decides_when_asked() {
+        fn take(_: &Path, diff: Option<&str>) -> Result<Resolution> {
+            assert!(diff.unwrap().contains("+b"));
+            Ok(Resolution::Take)
+        }
+
+        assert_eq!(
+            conflict(b"a\n", b"b\n", ConflictPolicy::Ask(take)).unwrap(),
+            b"b\n"
+        );
+    }
+
+    #[test]
+    fn binary_files_cannot_have_markers() {
+        let error = conflict(b"\0a", b"\0b", ConflictPolicy::Markers).unwrap_err();
+
+        assert_eq!(error.code(), "ERR_TADA_MERGE");
+        assert_eq!(
+            conflict(b"\0a", b"\0b", ConflictPolicy::Take).unwrap(),
+            b"\0b"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+#![deny(clippy::all)]
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyKind {
+    /// Runtime dependencies, written to `dependencies`.
+    #[serde(rename = "dependencies")]
+    Dependencies,
+    /// Build and tooling dependencies, written to `devDependencies`.
+    #[serde(rename = "devDependencies")]
+    DevDependencies,
+}
+
+impl fmt::Display for DependencyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DependencyKind::Dependencies => write!(f, "dependencies"),
+            DependencyKind::DevDependencies => write!(f, "devDependencies"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DependencyMove {
+    pub name: String,
+    pub from: DependencyKind,
+    pub to: DependencyKind,
+    /// Version kept in the destination map.
+    pub version: String,
+    /// Version dropped from the source map.
+    pub dropped_version: String,
+}
+
+impl fmt::Display for DependencyMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.name, self.from, self.to)?;
+
+        if self.version != self.dropped_version {
+            write!(
+                f,
+                " (kept {}, dropped {})",
+                self.version, self.dropped_version
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes packages listed in both `dependencies` and `devDependencies`.
+///
+/// A package keeps the kind requested for it in `intents` (collected from
+/// the addons' `dependencyKinds`); otherwise runtime wins and the entry is
+/// dropped from `devDependencies`. Returns every package that was moved.
+pub fn dedupe_dependencies(
+    dependencies: &mut BTreeMap<String, String>,
+    dev_dependencies: &mut BTreeMap<String, String>,
+    intents: &BTreeMap<String, DependencyKind>,
+) -> Vec<DependencyMove> {
+    let duplicated = dependencies
+        .keys()
+        .filter(|name| dev_dependencies.contains_key(*name))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut moves: Vec<DependencyMove> = Vec::new();
+
+    for name in duplicated {
+        let kind = intents
+            .get(&name)
+            .copied()
+            .unwrap_or(DependencyKind::Dependencies);
+
+        let (version, dropped_version, from) = match kind {
+            DependencyKind::Dependencies => (
+                dependencies[&name].clone(),
+                dev_dependencies.remove(&name).unwrap_or_default(),
+                DependencyKind::DevDependencies,
+            ),
+            DependencyKind::DevDependencies => (
+                dev_dependencies[&name].clone(),
+                dependencies.remove(&name).unwrap_or_default(),
+                DependencyKind::Dependencies,
+            ),
+        };
+
+        moves.push(DependencyMove {
+            name,
+            from,
+            to: kind,
+            version,
+            dropped_version,
+        });
+    }
+
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(name, version)| (name.to_string(), version.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn runtime_wins_by_default() {
+        let mut dependencies = map(&[("react", "^19.0.0")]);
+        let mut dev_dependencies = map(&[("react", "^18.0.0"), ("vite", "^6.0.0")]);
+
+        let moves = dedupe_dependencies(&mut dependencies, &mut dev_dependencies, &BTreeMap::new());
+
+        assert_eq!(dependencies, map(&[("react", "^19.0.0")]));
+        assert_eq!(dev_dependencies, map(&[("vite", "^6.0.0")]));
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].from, DependencyKind::DevDependencies);
+        assert_eq!(moves[0].to, DependencyKind::Dependencies);
+        assert_eq!(
+            moves[0].to_string(),
+            "react: devDependencies -> dependencies (kept ^19.0.0, dropped ^18.0.0)"
+        );
+    }
+
+    #[test]
+    fn intents_pick_the_kind() {
+        let mut dependencies = map(&[("typescript", "^5.0.0")]);
+        let mut dev_dependencies = map(&[("typescript", "^5.0.0")]);
+        let intents = BTreeMap::from([("typescript".to_string(), DependencyKind::DevDependencies)]);
+
+        let moves = dedupe_dependencies(&mut dependencies, &mut dev_dependencies, &intents);
+
+        assert!(dependencies.is_empty());
+        assert_eq!(dev_dependencies, map(&[("typescript", "^5.0.0")]));
+        assert_eq!(
+            moves[0].to_string(),
+            "typescript: dependencies -> devDependencies"
+        );
+    }
+
+    #[test]
+    fn unique_packages_are_untouched() {
+        let mut dependencies = map(&[("react", "^19.0.0")]);
+        let mut dev_dependencies = map(&[("vite", "^6.0.0")]);
+        let intents = BTreeMap::from([("react".to_string(), DependencyKind::DevDependencies)]);
+
+        let moves = dedupe_dependencies(&mut dependencies, &mut dev_dependencies, &intents);
+
+        assert!(moves.is_empty());
+        assert_eq!(dependencies, map(&[("react", "^19.0.0")]));
+        assert_eq!(dev_dependencies, map(&[("vite", "^6.0.0")]));
+    }
+}
@@ -0,0 +1,175 @@
+#![deny(clippy::all)]
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+
+/// An error found while reading a JSON file, pointing at where it happened.
+#[derive(Debug)]
+pub struct JsonDiagnostic {
+    pub path: PathBuf,
+    /// Line and column, both 1-based, when the file could be read.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for JsonDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+
+        if let Some((line, column)) = self.location {
+            write!(f, ":{}:{}", line, column)?;
+        }
+
+        write!(f, ": {}", self.message)?;
+
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{}`?)", suggestion)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for JsonDiagnostic {}
+
+pub fn parse_json_file<T>(path: &OsStr) -> Result<T, JsonDiagnostic>
+where
+    T: DeserializeOwned,
+{
+    let file = fs::File::open(path).map_err(|e| JsonDiagnostic {
+        path: PathBuf::from(path),
+        location: None,
+        message: format!("Error reading file: {}", e),
+        suggestion: None,
+    })?;
+
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+        let message = e.to_string();
+
+        // `serde_json` appends the location to the message.
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _)) => message.to_string(),
+            None => message,
+        };
+
+        JsonDiagnostic {
+            path: PathBuf::from(path),
+            location: (e.line() > 0).then(|| (e.line(), e.column())),
+            suggestion: suggest(&message),
+            message,
+        }
+    })
+}
+
+/// Line and column, both 1-based, of the `value` string given to `key` in
+/// the JSON `text`: the first one after the key. For values that parse but
+/// are wrong, e.g. a missing template.
+pub fn locate_value(text: &str, key: &str, value: &str) -> Option<(usize, usize)> {
+    let key = serde_json::to_string(key).ok()?;
+    let value = serde_json::to_string(value).ok()?;
+
+    let key_index = text
+        .find(&format!("{}:", key))
+        .or_else(|| text.find(&key))?;
+    let index = key_index + text[key_index..].find(&value)?;
+
+    let before = &text[..index];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |x| x + 1) + 1;
+
+    Some((line, column))
+}
+
+/// Suggests the closest expected name for `unknown field` and
+/// `unknown variant` errors, e.g. `skip_if_exists` for `skip-if-exists`.
+fn suggest(message: &str) -> Option<String> {
+    let rest = message
+        .strip_prefix("unknown field `")
+        .or_else(|| message.strip_prefix("unknown variant `"))?;
+
+    let (unknown, expected) = rest.split_once('`')?;
+    let unknown = normalize(unknown);
+
+    expected
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(|candidate| {
+            (
+                strsim::jaro_winkler(&unknown, &normalize(candidate)),
+                candidate,
+            )
+        })
+        .filter(|(score, _)| *score > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+fn normalize(name: &str) -> String {
+    name.to_lowercase().replace('-', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::fs::TadaJson;
+
+    #[test]
+    fn suggest_closest_name() {
+        assert_eq!(
+            suggest("unknown variant `skip-if-exists`, expected one of `overwrite`, `skip_if_exists`, `prepend`, `append`"),
+            Some("skip_if_exists".to_string())
+        );
+        assert_eq!(
+            suggest("unknown field `tempaltes`, expected `templates` or `entries`"),
+            Some("templates".to_string())
+        );
+        assert_eq!(
+            suggest("unknown field `Entries`, expected `templates` or `entries`"),
+            Some("entries".to_string())
+        );
+    }
+
+    #[test]
+    fn suggest_nothing_for_distant_names() {
+        assert_eq!(
+            suggest("unknown field `description`, expected `templates` or `entries`"),
+            None
+        );
+        assert_eq!(suggest("missing field `templates`"), None);
+        assert_eq!(suggest("unknown field `x`"), None);
+    }
+
+    #[test]
+    fn values_are_located_after_their_key() {
+        let text = "{\n  \"template\": \"react\",\n  \"addons\": [\"react\", \"tailwind\"]\n}";
+
+        assert_eq!(locate_value(text, "template", "react"), Some((2, 15)));
+        assert_eq!(locate_value(text, "addons", "react"), Some((3, 14)));
+        assert_eq!(locate_value(text, "addons", "tailwind"), Some((3, 23)));
+        assert_eq!(locate_value(text, "addons", "biome"), None);
+    }
+
+    #[test]
+    fn parse_errors_are_located() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tada.json");
+        fs::write(&path, "{\n  \"templates\": [],\n  \"entires\": []\n}").unwrap();
+
+        let error = parse_json_file::<TadaJson>(path.as_os_str()).unwrap_err();
+
+        assert_eq!(error.location, Some((3, 12)));
+        assert!(error.message.starts_with("unknown field `entires`"));
+        assert_eq!(error.suggestion.as_deref(), Some("entries"));
+
+        let error = error.to_string();
+        assert!(error.contains("tada.json:3:12: unknown field `entires`, expected"));
+        assert!(error.ends_with(" (did you mean `entries`?)"));
+    }
+}
@@ -1,21 +1,29 @@
 #![deny(clippy::all)]
 
-use fs_extra::dir;
 use fs_extra::dir::{ls, DirEntryAttr, DirEntryValue};
-use fs_extra::error::{Error, ErrorKind, Result};
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, HashSet};
 use std::ffi::{OsStr, OsString};
 use std::fmt::Debug;
 use std::fs;
-use std::io::{BufReader, Read, Seek, Write};
-use std::path::Path;
-use std::u64;
+use std::path::{Path, PathBuf};
 
-use anyhow::Context;
 use diffy::{apply, create_patch};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+use super::config::PACKAGE_MANAGERS;
+use super::conflict::{resolve_conflict, ConflictPolicy};
+use super::deps::DependencyKind;
+use super::diagnostic::{locate_value, parse_json_file, JsonDiagnostic};
+use crate::error::{Result, TadaError};
+
+pub const TADA_JSON: &str = "tada.json";
+/// Files of a template that are not copied, in `.gitignore` syntax.
+pub const TADAIGNORE: &str = ".tadaignore";
+/// Dotfiles published as `_<name>`, since npm drops some of them from
+/// packages. They get their dot back while copying.
+pub const DOTFILES: [&str; 5] = ["gitignore", "gitattributes", "npmrc", "editorconfig", "env"];
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum AddonFileCopyType {
     /// Sets the option true for overwrite existing files.
@@ -33,6 +41,7 @@ pub enum AddonFileCopyType {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct Entry {
     pub input: String,
     pub output: String,
@@ -40,20 +49,78 @@ pub struct Entry {
     pub mode: Option<AddonFileCopyType>,
 }
 
+/// Shell commands run in the new app directory.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+#[allow(non_snake_case)]
+pub struct Hooks {
+    /// Once every file is copied and `package.json` is written.
+    #[serde(default)]
+    pub postCopy: Vec<String>,
+    /// Once the dependencies are installed, skipped when they are not.
+    #[serde(default)]
+    pub postInstall: Vec<String>,
+}
+
+/// Schema: `cli/tada.schema.json`, keep both in sync.
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 #[allow(non_snake_case)]
 pub struct TadaJson {
-    templates: Vec<String>,
+    #[serde(rename = "$schema")]
+    schema: Option<String>,
+
+    pub description: Option<String>,
+    /// Group the addon is listed under, e.g. `styling` or `linting`.
+    pub category: Option<String>,
+
+    pub templates: Vec<String>,
     pub entries: Vec<Entry>,
+
+    /// Kind a package should end up in when it is listed under both
+    /// `dependencies` and `devDependencies` after merging.
+    pub dependencyKinds: Option<BTreeMap<String, DependencyKind>>,
+
+    pub hooks: Option<Hooks>,
+    /// File names renamed while copying, on top of the `_<dotfile>` ones.
+    pub renames: Option<BTreeMap<String, String>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Optional metadata of a template, read from its own `tada.json`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateJson {
+    #[serde(rename = "$schema")]
+    schema: Option<String>,
+
+    /// Label shown instead of the directory name.
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Templates are listed by ascending order, then by name.
+    pub order: Option<i64>,
+    /// Hidden templates are not listed, but presets can still use them.
+    #[serde(default)]
+    pub hidden: bool,
+    #[serde(default)]
+    pub experimental: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Also skip the files ignored by the template `.gitignore`.
+    #[serde(default)]
+    pub gitignore: bool,
+    pub hooks: Option<Hooks>,
+    /// File names renamed while copying, on top of the `_<dotfile>` ones.
+    #[serde(default)]
+    pub renames: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[allow(non_snake_case)]
 pub struct PackageJson {
     pub name: String,
 
     #[serde(flatten)]
-    other: HashMap<String, serde_json::Value>,
+    other: serde_json::Map<String, serde_json::Value>,
 
     pub scripts: Option<BTreeMap<String, String>>,
 
@@ -61,274 +128,566 @@ pub struct PackageJson {
     pub devDependencies: Option<BTreeMap<String, String>>,
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+#[allow(non_snake_case)]
+pub struct PresetJson {
+    #[serde(rename = "$schema")]
+    schema: Option<String>,
+
+    pub description: Option<String>,
+    pub template: String,
+    #[serde(default)]
+    pub addons: Vec<String>,
+
+    pub install: Option<bool>,
+    pub packageManager: Option<String>,
+}
+
+/// A generation checked against a snapshot, declared in `snapshots/*.json`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+#[allow(non_snake_case)]
+pub struct ScenarioJson {
+    pub description: Option<String>,
+    pub template: String,
+    #[serde(default)]
+    pub addons: Vec<String>,
+    /// `name` of the generated `package.json`, `app` by default.
+    pub packageName: Option<String>,
+    /// `--on-conflict` policy, `mode` by default. `ask` is refused, scenarios
+    /// run without prompts.
+    pub onConflict: Option<String>,
+    /// Leave the template lockfiles out, as in a workspace.
+    #[serde(default)]
+    pub skipLockfiles: bool,
+    /// Resolve the dependencies against the registry, as `--latest` does.
+    #[serde(default)]
+    pub latest: bool,
+}
+
+#[derive(Debug, Clone)]
 pub struct Details {
     pub name: String,
     pub path: OsString,
+    /// Catalog root the entry was found in.
+    pub root: OsString,
 }
 
-pub fn read_json_file<T>(path: &OsStr) -> T
+/// An entry hidden by one with the same name in a root of higher precedence.
+#[derive(Debug)]
+pub struct Shadowed {
+    pub name: String,
+    pub root: OsString,
+    pub by: OsString,
+}
+
+pub fn read_json_file<T>(path: &OsStr) -> Result<T>
 where
     T: DeserializeOwned,
 {
-    let file = fs::File::open(path)
-        .with_context(|| format!("Error reading file: {}", path.to_str().unwrap()))
-        .unwrap();
+    Ok(parse_json_file(path)?)
+}
 
-    let reader = BufReader::new(file);
+/// Defaults when the template has no `tada.json`.
+pub fn read_template_json(path: &OsStr) -> Result<TemplateJson, JsonDiagnostic> {
+    let template_json_path = Path::new(path).join(TADA_JSON);
 
-    let package_json: T = serde_json::from_reader(reader)
-        .with_context(|| "Error parsing JSON")
-        .unwrap();
+    if !template_json_path.is_file() {
+        return Ok(TemplateJson::default());
+    }
 
-    package_json
+    parse_json_file(template_json_path.as_os_str())
 }
 
-pub fn get_templates(path: &OsStr, templates: &mut BTreeMap<String, OsString>) {
-    let path_to = Path::new(&path);
-
+/// Lists the entries of a catalog directory as `(name, path)` pairs.
+fn list_dir(path: &Path) -> Result<Vec<(String, String)>> {
     let mut config = HashSet::new();
-    config.insert(DirEntryAttr::Name);
+    config.insert(DirEntryAttr::FullName);
     config.insert(DirEntryAttr::Path);
 
-    let templates_dir = ls(path_to, &config)
-        .with_context(|| format!("Error reading templates directory: {:?}", path_to))
-        .unwrap();
-
-    for item in templates_dir.items {
-        let template_path = item
-            .get(&DirEntryAttr::Path)
-            .with_context(|| "Error reading path")
-            .unwrap();
+    let dir = ls(path, &config).map_err(TadaError::fs(format!(
+        "Error reading directory: {:?}",
+        path
+    )))?;
 
-        let template_name = item
-            .get(&DirEntryAttr::Name)
-            .with_context(|| "Error reading name")
-            .unwrap();
+    let mut entries: Vec<(String, String)> = Vec::new();
 
-        if let DirEntryValue::String(path) = template_path {
-            if let DirEntryValue::String(name) = template_name {
-                templates.insert(name.to_string(), OsString::from(path));
-            }
+    for item in dir.items {
+        if let (Some(DirEntryValue::String(name)), Some(DirEntryValue::String(path))) = (
+            item.get(&DirEntryAttr::FullName),
+            item.get(&DirEntryAttr::Path),
+        ) {
+            entries.push((name.clone(), path.clone()));
         }
     }
+
+    Ok(entries)
 }
 
-pub fn get_filtered_addons(
-    path: &OsStr,
-    template_name: String,
-    addons: &mut BTreeMap<String, OsString>,
-) {
-    let mut config = HashSet::new();
-    config.insert(DirEntryAttr::Name);
-    config.insert(DirEntryAttr::Path);
+pub fn get_templates(
+    root: &OsStr,
+    templates: &mut BTreeMap<String, Details>,
+    shadowed: &mut Vec<Shadowed>,
+) -> Result<()> {
+    let path_to = Path::new(&root).join("templates");
 
-    let dirs = ls(path, &config)
-        .with_context(|| format!("Error reading addons directory: {:?}", path))
-        .unwrap();
+    if !path_to.is_dir() {
+        return Ok(());
+    }
+
+    for (name, path) in list_dir(&path_to)? {
+        insert_details(templates, shadowed, &name, &path, root);
+    }
+
+    Ok(())
+}
 
-    for item in dirs.items {
-        let addon_path = item
-            .get(&DirEntryAttr::Path)
-            .with_context(|| "Addons: Error reading path")
-            .unwrap();
+/// Every addon of `root`, whatever templates it is compatible with.
+pub fn get_addons(
+    root: &OsStr,
+    addons: &mut BTreeMap<String, Details>,
+    shadowed: &mut Vec<Shadowed>,
+    invalid: &mut Vec<JsonDiagnostic>,
+) -> Result<()> {
+    list_addons(root, |_| true, addons, shadowed, invalid)
+}
+
+pub fn get_filtered_addons(
+    root: &OsStr,
+    template_name: String,
+    addons: &mut BTreeMap<String, Details>,
+    shadowed: &mut Vec<Shadowed>,
+    invalid: &mut Vec<JsonDiagnostic>,
+) -> Result<()> {
+    list_addons(
+        root,
+        |tada_json| {
+            tada_json.templates.contains(&"all".to_string())
+                || tada_json.templates.contains(&template_name)
+        },
+        addons,
+        shadowed,
+        invalid,
+    )
+}
 
-        let addon_name = item
-            .get(&DirEntryAttr::Name)
-            .with_context(|| "Addons: Error reading name")
-            .unwrap();
+fn list_addons(
+    root: &OsStr,
+    filter: impl Fn(&TadaJson) -> bool,
+    addons: &mut BTreeMap<String, Details>,
+    shadowed: &mut Vec<Shadowed>,
+    invalid: &mut Vec<JsonDiagnostic>,
+) -> Result<()> {
+    let path_to = Path::new(&root).join("addons");
+
+    if !path_to.is_dir() {
+        return Ok(());
+    }
 
-        if let DirEntryValue::String(path) = addon_path {
-            if let DirEntryValue::String(name) = addon_name {
-                let tada_json_path = Path::new(&path).join("tada.json");
+    for (name, path) in list_dir(&path_to)? {
+        if !Path::new(&path).is_dir() {
+            continue;
+        }
 
-                let tada_json: TadaJson = read_json_file(tada_json_path.as_os_str());
+        let tada_json_path = Path::new(&path).join(TADA_JSON);
 
-                if tada_json.templates.contains(&"all".to_string())
-                    || tada_json.templates.contains(&template_name)
-                {
-                    addons.insert(name.to_string(), OsString::from(path));
-                }
+        let tada_json: TadaJson = match parse_json_file(tada_json_path.as_os_str()) {
+            Ok(tada_json) => tada_json,
+            Err(e) => {
+                invalid.push(e);
+                continue;
             }
+        };
+
+        if filter(&tada_json) {
+            insert_details(addons, shadowed, &name, &path, root);
         }
     }
-}
 
-pub fn get_items_in_template(path: &OsStr, ignore: Vec<String>) -> Vec<OsString> {
-    let path_to = Path::new(&path);
+    Ok(())
+}
 
-    let mut config = HashSet::new();
-    config.insert(DirEntryAttr::Name);
-    config.insert(DirEntryAttr::Path);
+pub fn get_presets(
+    root: &OsStr,
+    presets: &mut BTreeMap<String, Details>,
+    shadowed: &mut Vec<Shadowed>,
+) -> Result<()> {
+    get_json_files(root, "presets", presets, shadowed)
+}
 
-    let mut items: Vec<OsString> = Vec::new();
+pub fn get_scenarios(
+    root: &OsStr,
+    scenarios: &mut BTreeMap<String, Details>,
+    shadowed: &mut Vec<Shadowed>,
+) -> Result<()> {
+    get_json_files(root, "snapshots", scenarios, shadowed)
+}
 
-    let dirs = ls(path_to, &config)
-        .with_context(|| format!("Error reading addons directory: {:?}", path_to))
-        .unwrap();
+/// Lists the `*.json` files of `root/directory`, keyed by file stem.
+fn get_json_files(
+    root: &OsStr,
+    directory: &str,
+    entries: &mut BTreeMap<String, Details>,
+    shadowed: &mut Vec<Shadowed>,
+) -> Result<()> {
+    let path_to = Path::new(&root).join(directory);
+
+    if !path_to.is_dir() {
+        return Ok(());
+    }
 
-    for item in dirs.items {
-        let item_path = item
-            .get(&DirEntryAttr::Path)
-            .with_context(|| "Addons: Error reading path")
-            .unwrap();
+    let dir = fs::read_dir(&path_to).map_err(TadaError::fs(format!(
+        "Error reading {} directory: {:?}",
+        directory, path_to
+    )))?;
 
-        let item_name = item
-            .get(&DirEntryAttr::Name)
-            .with_context(|| "Addons: Error reading name")
-            .unwrap();
+    for item in dir.flatten() {
+        let path = item.path();
 
-        if let DirEntryValue::String(path) = item_path {
-            if let DirEntryValue::String(name) = item_name {
-                if ignore.contains(name) {
-                    continue;
-                }
+        if path.extension() != Some(OsStr::new("json")) {
+            continue;
+        }
 
-                items.push(OsString::from(path));
-            }
+        if let (Some(name), Some(path)) = (path.file_stem().and_then(|x| x.to_str()), path.to_str())
+        {
+            insert_details(entries, shadowed, name, path, root);
         }
     }
 
-    items
+    Ok(())
 }
 
-pub fn copy_addon_file<P, Q>(from: P, to: Q, mode: &Option<AddonFileCopyType>) -> Result<u64>
-where
-    P: AsRef<Path>,
-    Q: AsRef<Path>,
-{
-    let from = from.as_ref();
-    if !from.exists() {
-        if let Some(msg) = from.to_str() {
-            let msg = format!("Path \"{}\" does not exist or you don't have access!", msg);
-            return Err(Error::new(ErrorKind::NotFound, &msg));
-        }
-        return Err(Error::new(
-            ErrorKind::NotFound,
-            "Path does not exist or you don't have access!",
+/// Checks that the preset template exists and that every preset addon is
+/// compatible with it. Problems point at the value in the preset file at
+/// `path`.
+pub fn validate_preset(
+    path: &Path,
+    preset: &PresetJson,
+    templates: &BTreeMap<String, Details>,
+    roots: &[PathBuf],
+) -> Result<()> {
+    let text = fs::read_to_string(path).unwrap_or_default();
+    let problem = |key: &str, value: &str, message: String| {
+        TadaError::Manifest(JsonDiagnostic {
+            path: path.to_path_buf(),
+            location: locate_value(&text, key, value),
+            message,
+            suggestion: None,
+        })
+    };
+
+    if !templates.contains_key(&preset.template) {
+        return Err(problem(
+            "template",
+            &preset.template,
+            format!("template `{}` not found", preset.template),
         ));
     }
 
-    if !from.is_file() {
-        if let Some(msg) = from.to_str() {
-            let msg = format!("Path \"{}\" is not a file!", msg);
-            return Err(Error::new(ErrorKind::InvalidFile, &msg));
+    if let Some(package_manager) = &preset.packageManager {
+        if !PACKAGE_MANAGERS.contains(&package_manager.as_str()) {
+            return Err(problem(
+                "packageManager",
+                package_manager,
+                format!(
+                    "invalid package manager `{}`, expected one of: {}",
+                    package_manager,
+                    PACKAGE_MANAGERS.join(", ")
+                ),
+            ));
         }
-        return Err(Error::new(ErrorKind::InvalidFile, "Path is not a file!"));
     }
 
-    let to = to.as_ref();
+    let mut addons: BTreeMap<String, Details> = BTreeMap::new();
+    let mut shadowed: Vec<Shadowed> = Vec::new();
+    let mut invalid: Vec<JsonDiagnostic> = Vec::new();
+    for root in roots {
+        get_filtered_addons(
+            root.as_os_str(),
+            preset.template.clone(),
+            &mut addons,
+            &mut shadowed,
+            &mut invalid,
+        )?;
+    }
 
-    if !to.exists() {
-        match std::fs::copy(from, to) {
-            Ok(_) => return Ok(0),
-            Err(e) => {
-                let msg = format!("Error creating file: {:?}", e);
-                return Err(Error::new(ErrorKind::InvalidFile, &msg));
-            }
+    for addon in &preset.addons {
+        if !addons.contains_key(addon) {
+            return Err(problem(
+                "addons",
+                addon,
+                format!(
+                    "addon `{}` is not available for `{}`",
+                    addon, preset.template
+                ),
+            ));
         }
     }
 
-    if let Some(mode) = mode {
-        match mode {
-            AddonFileCopyType::Overwrite => return Ok(std::fs::copy(from, to)?),
-            AddonFileCopyType::SkipIfExists => return Ok(0),
-            _ => {}
-        }
+    Ok(())
+}
+
+/// Roots are read in order of precedence, so an entry already found in an
+/// earlier root shadows the one being inserted.
+fn insert_details(
+    entries: &mut BTreeMap<String, Details>,
+    shadowed: &mut Vec<Shadowed>,
+    name: &str,
+    path: &str,
+    root: &OsStr,
+) {
+    if let Some(existing) = entries.get(name) {
+        shadowed.push(Shadowed {
+            name: name.to_string(),
+            root: root.to_os_string(),
+            by: existing.root.clone(),
+        });
+        return;
     }
 
-    let from_file = fs::File::open(from);
-    let from_content = match from_file {
-        Ok(file) => {
-            let mut reader = BufReader::new(file);
-            let mut content = String::new();
-            match reader.read_to_string(&mut content) {
-                Ok(_) => content,
-                Err(e) => {
-                    let msg = format!("Error reading file: {:?}", e);
-                    return Err(Error::new(ErrorKind::InvalidFile, &msg));
-                }
-            }
-        }
-        Err(e) => {
-            let msg = format!("Error reading file: {:?}", e);
-            return Err(Error::new(ErrorKind::InvalidFile, &msg));
-        }
-    };
+    entries.insert(
+        name.to_string(),
+        Details {
+            name: name.to_string(),
+            path: OsString::from(path),
+            root: root.to_os_string(),
+        },
+    );
+}
+
+/// Lists the files under `dir`, recursively.
+pub fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir).map_err(TadaError::fs(format!("Error reading {:?}", dir)))?;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
 
-    let mut to_file = match fs::OpenOptions::new().read(true).write(true).open(to) {
-        Ok(file) => file,
-        Err(e) => {
-            let msg = format!("Error creating file: {:?}", e);
-            return Err(Error::new(ErrorKind::InvalidFile, &msg));
+        if path.is_dir() {
+            list_files(&path, files)?;
+        } else {
+            files.push(path);
         }
+    }
+
+    Ok(())
+}
+
+/// Name a template or addon file is copied as: the one mapped in `renames`,
+/// `.<name>` for the `_<name>` form of the usual dotfiles, or its own.
+pub fn renamed(name: &OsStr, renames: &BTreeMap<String, String>) -> OsString {
+    let Some(name) = name.to_str() else {
+        return name.to_os_string();
     };
 
-    let mut reader = BufReader::new(&to_file);
-    let mut to_content = String::new();
+    if let Some(renamed) = renames.get(name) {
+        return OsString::from(renamed);
+    }
+
+    match name.strip_prefix('_') {
+        Some(dotfile) if DOTFILES.contains(&dotfile) => OsString::from(format!(".{}", dotfile)),
+        _ => OsString::from(name),
+    }
+}
+
+const BOM: char = '\u{feff}';
 
-    match reader.read_to_string(&mut to_content) {
-        Ok(_) => {
-            to_file.rewind().unwrap();
-        }
-        Err(e) => {
-            let msg = format!("Error reading file: {:?}", e);
-            return Err(Error::new(ErrorKind::InvalidFile, &msg));
+/// Conventions of a text file, kept when an addon is merged into it.
+#[derive(Debug, Clone, Copy)]
+pub struct TextStyle {
+    crlf: bool,
+    bom: bool,
+    final_newline: bool,
+}
+
+impl TextStyle {
+    /// Line endings follow most lines of the file, `\n` on a tie.
+    pub fn detect(text: &str) -> TextStyle {
+        let lines = text.matches('\n').count();
+        let crlf_lines = text.matches("\r\n").count();
+
+        TextStyle {
+            crlf: crlf_lines * 2 > lines,
+            bom: text.starts_with(BOM),
+            final_newline: text.ends_with('\n'),
         }
-    };
+    }
 
-    if let Some(mode) = mode {
-        match mode {
-            AddonFileCopyType::Prepend => {
-                match to_file.write_all(format!("{}\n{}", from_content, to_content).as_bytes()) {
-                    Ok(_) => {
-                        return Ok(0);
-                    }
-                    Err(e) => {
-                        let msg = format!("Error writing file: {:?}", e);
-                        return Err(Error::new(ErrorKind::InvalidFile, &msg));
-                    }
-                };
-            }
-            AddonFileCopyType::Append => {
-                match to_file.write_all(format!("{}\n{}", to_content, from_content).as_bytes()) {
-                    Ok(_) => {
-                        return Ok(0);
-                    }
-                    Err(e) => {
-                        let msg = format!("Error writing file: {:?}", e);
-                        return Err(Error::new(ErrorKind::InvalidFile, &msg));
-                    }
-                };
+    /// Drops the BOM and converts the line endings to `\n`.
+    pub fn normalize(text: &str) -> String {
+        text.strip_prefix(BOM).unwrap_or(text).replace("\r\n", "\n")
+    }
+
+    /// Inverse of `normalize`, with a final newline when the destination
+    /// had one, and without otherwise. Only that newline is added or
+    /// removed, trailing blank lines are content.
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+
+        match (self.final_newline, text.ends_with('\n')) {
+            (true, false) => text.push('\n'),
+            (false, true) => {
+                text.pop();
             }
             _ => {}
         }
-    }
 
-    // If the destination file is not empty, create a patch with it
-    // as the original content and the new content as the modified
-    // content.
-    let patch = create_patch(&to_content, &from_content);
-    let applied = match apply(&to_content, &patch) {
-        Ok(result) => result,
-        Err(e) => {
-            let msg = format!("Error applying patch: {:?}", e);
-            return Err(Error::new(ErrorKind::InvalidFile, &msg));
+        if self.crlf {
+            text = text.replace('\n', "\r\n");
         }
-    };
 
-    match to_file.write_all(applied.as_bytes()) {
-        Ok(_) => {
-            return Ok(0);
+        if self.bom {
+            text.insert(0, BOM);
         }
-        Err(e) => {
-            let msg = format!("Error writing file: {:?}", e);
-            return Err(Error::new(ErrorKind::InvalidFile, &msg));
+
+        text
+    }
+}
+
+/// `first` then `second`, on their own lines.
+fn join_lines(first: &str, second: &str) -> String {
+    if first.is_empty() || first.ends_with('\n') {
+        return format!("{}{}", first, second);
+    }
+
+    format!("{}\n{}", first, second)
+}
+
+/// Reads a file merged into `destination`. Prepending, appending and
+/// patching only make sense for text, binary files can only be overwritten
+/// or skipped.
+fn read_text(path: &Path, destination: &Path) -> Result<String> {
+    let bytes = fs::read(path).map_err(TadaError::fs(format!("Error reading {:?}", path)))?;
+
+    if content_inspector::inspect(&bytes).is_binary() {
+        return Err(TadaError::Merge {
+            path: destination.to_path_buf(),
+            message: format!(
+                "{:?} is a binary file, use the `overwrite` or `skip_if_exists` mode",
+                path
+            ),
+        });
+    }
+
+    String::from_utf8(bytes).map_err(|_| TadaError::Merge {
+        path: destination.to_path_buf(),
+        message: format!("{:?} is not UTF-8 text", path),
+    })
+}
+
+pub fn copy_addon_file<P, Q>(
+    from: P,
+    to: Q,
+    mode: &Option<AddonFileCopyType>,
+    policy: ConflictPolicy,
+) -> Result<u64>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let from = from.as_ref();
+    let to = to.as_ref();
+
+    if !from.is_file() {
+        return Err(TadaError::Catalog(format!(
+            "Addon entry {:?} does not exist or is not a file",
+            from
+        )));
+    }
+
+    if !to.exists() {
+        return fs::copy(from, to).map_err(TadaError::fs(format!("Error copying {:?}", from)));
+    }
+
+    match mode {
+        Some(AddonFileCopyType::Prepend | AddonFileCopyType::Append) => {}
+        _ if !matches!(policy, ConflictPolicy::Mode) => return resolve_conflict(from, to, policy),
+        Some(AddonFileCopyType::Overwrite) => {
+            return fs::copy(from, to).map_err(TadaError::fs(format!("Error copying {:?}", from)));
         }
+        Some(AddonFileCopyType::SkipIfExists) => return Ok(0),
+        _ => {}
+    }
+
+    let from_content = read_text(from, to)?;
+    let to_content = read_text(to, to)?;
+
+    // Merged with `\n` line endings and without BOM, then written back in
+    // the style of the destination.
+    let style = match to_content.is_empty() {
+        true => TextStyle::detect(&from_content),
+        false => TextStyle::detect(&to_content),
     };
+    let from_content = TextStyle::normalize(&from_content);
+    let to_content = TextStyle::normalize(&to_content);
+
+    let merged = match mode {
+        Some(AddonFileCopyType::Prepend) => join_lines(&from_content, &to_content),
+        Some(AddonFileCopyType::Append) => join_lines(&to_content, &from_content),
+        // If the destination file is not empty, create a patch with it
+        // as the original content and the new content as the modified
+        // content.
+        _ => {
+            let patch = create_patch(&to_content, &from_content);
+            apply(&to_content, &patch).map_err(|e| TadaError::Merge {
+                path: to.to_path_buf(),
+                message: e.to_string(),
+            })?
+        }
+    };
+
+    fs::write(to, style.apply(&merged))
+        .map_err(TadaError::fs(format!("Error writing {:?}", to)))?;
+
+    Ok(0)
+}
+
+/// Copies the `from` directory into `to` as a whole, renaming on the way.
+/// Entry modes only apply to single files: an existing file fails the copy,
+/// unless `policy` resolves the conflict.
+fn copy_addon_dir(
+    from: &Path,
+    to: &Path,
+    renames: &BTreeMap<String, String>,
+    policy: ConflictPolicy,
+) -> Result<u64> {
+    fs::create_dir_all(to).map_err(TadaError::fs(format!("Error creating directory: {:?}", to)))?;
+
+    let entries = fs::read_dir(from).map_err(TadaError::fs(format!("Error reading {:?}", from)))?;
+
+    let mut result: u64 = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let destination = to.join(renamed(&entry.file_name(), renames));
+
+        if path.is_dir() {
+            result += copy_addon_dir(&path, &destination, renames, policy)?;
+        } else if !destination.exists() {
+            result += fs::copy(&path, &destination)
+                .map_err(TadaError::fs(format!("Error copying {:?}", path)))?;
+        } else if !matches!(policy, ConflictPolicy::Mode) {
+            result += resolve_conflict(&path, &destination, policy)?;
+        } else {
+            return Err(TadaError::Merge {
+                path: destination,
+                message: format!(
+                    "{:?} is part of a directory entry and the file already exists",
+                    path
+                ),
+            });
+        }
+    }
+
+    Ok(result)
 }
 
-pub fn copy_addon_items<P, Q>(from: &[P], to: Q, mode: &Option<AddonFileCopyType>) -> Result<u64>
+pub fn copy_addon_items<P, Q>(
+    from: &[P],
+    to: Q,
+    mode: &Option<AddonFileCopyType>,
+    renames: &BTreeMap<String, String>,
+    policy: ConflictPolicy,
+) -> Result<u64>
 where
     P: AsRef<Path>,
     Q: AsRef<Path>,
@@ -336,16 +695,122 @@ where
     let mut result: u64 = 0;
     for item in from {
         let item = item.as_ref();
+        let Some(file_name) = item.file_name() else {
+            return Err(TadaError::Catalog(format!(
+                "Addon entry {:?} has an invalid file name",
+                item
+            )));
+        };
+        let destination = to.as_ref().join(renamed(file_name, renames));
+
         if item.is_dir() {
-            result += dir::copy(item, &to, &Default::default()).unwrap();
-        } else if let Some(file_name) = item.file_name() {
-            if let Some(file_name) = file_name.to_str() {
-                result += copy_addon_file(item, to.as_ref().join(file_name), mode).unwrap();
-            }
+            result += copy_addon_dir(item, &destination, renames, policy)?;
         } else {
-            return Err(Error::new(ErrorKind::InvalidFileName, "Invalid file name"));
+            result += copy_addon_file(item, &destination, mode, policy)?;
         }
     }
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_metadata_rejects_unknown_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!read_template_json(dir.path().as_os_str()).unwrap().hidden);
+
+        fs::write(
+            dir.path().join(TADA_JSON),
+            "{\n  \"name\": \"React\",\n  \"hiden\": true\n}",
+        )
+        .unwrap();
+
+        let diagnostic = read_template_json(dir.path().as_os_str()).unwrap_err();
+        assert_eq!(diagnostic.location, Some((3, 10)));
+        assert_eq!(diagnostic.suggestion.as_deref(), Some("hidden"));
+    }
+
+    #[test]
+    fn preset_problems_are_located() {
+        let root = tempfile::tempdir().unwrap();
+        let preset_path = root.path().join("presets/react.json");
+        fs::create_dir_all(root.path().join("presets")).unwrap();
+        fs::create_dir_all(root.path().join("templates/react")).unwrap();
+        fs::create_dir_all(root.path().join("addons/biome")).unwrap();
+        fs::write(
+            root.path().join("addons/biome").join(TADA_JSON),
+            r#"{"templates": ["all"], "entries": []}"#,
+        )
+        .unwrap();
+
+        let mut templates: BTreeMap<String, Details> = BTreeMap::new();
+        get_templates(root.path().as_os_str(), &mut templates, &mut Vec::new()).unwrap();
+        let roots = [root.path().to_path_buf()];
+
+        fs::write(
+            &preset_path,
+            "{\n  \"template\": \"react\",\n  \"adons\": []\n}",
+        )
+        .unwrap();
+        let diagnostic = parse_json_file::<PresetJson>(preset_path.as_os_str()).unwrap_err();
+        assert_eq!(diagnostic.location, Some((3, 10)));
+        assert_eq!(diagnostic.suggestion.as_deref(), Some("addons"));
+
+        fs::write(
+            &preset_path,
+            "{\n  \"template\": \"react\",\n  \"addons\": [\"biome\", \"eslint\"]\n}",
+        )
+        .unwrap();
+        let preset = parse_json_file::<PresetJson>(preset_path.as_os_str()).unwrap();
+        let error = validate_preset(&preset_path, &preset, &templates, &roots).unwrap_err();
+        assert_eq!(error.code(), "ERR_TADA_MANIFEST");
+        assert!(
+            error
+                .to_string()
+                .contains("react.json:3:23: addon `eslint`"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn line_endings_follow_most_lines() {
+        assert!(TextStyle::detect("a\r\nb\r\nc\n").crlf);
+        assert!(!TextStyle::detect("a\nb\nc\r\n").crlf);
+        assert!(!TextStyle::detect("a\r\nb\n").crlf);
+        assert!(!TextStyle::detect("a").crlf);
+    }
+
+    #[test]
+    fn only_the_final_newline_is_changed() {
+        let style = TextStyle::detect("a\r\n");
+        assert_eq!(style.apply("a\n\n"), "a\r\n\r\n");
+        assert_eq!(style.apply("a"), "a\r\n");
+
+        let style = TextStyle::detect("\u{feff}a");
+        assert_eq!(style.apply("a\n\n"), "\u{feff}a\n");
+        assert_eq!(style.apply("a"), "\u{feff}a");
+    }
+
+    #[test]
+    fn appended_files_keep_the_destination_style() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("addon.css");
+        let to = dir.path().join("index.css");
+        fs::write(&from, "b\n\n").unwrap();
+        fs::write(&to, "\u{feff}a\r\n").unwrap();
+
+        copy_addon_file(
+            &from,
+            &to,
+            &Some(AddonFileCopyType::Append),
+            ConflictPolicy::Mode,
+        )
+        .unwrap();
+
+        assert_eq!(fs::read_to_string(&to).unwrap(), "\u{feff}a\r\nb\r\n\r\n");
+    }
+}
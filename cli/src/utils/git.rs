@@ -0,0 +1,79 @@
+#![deny(clippy::all)]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Why no repository is created for the new app.
+#[derive(Debug)]
+pub enum Skipped {
+    GitMissing,
+    InsideRepository(PathBuf),
+}
+
+/// Repository of the new app.
+#[derive(Debug)]
+pub struct Repository {
+    path: PathBuf,
+}
+
+fn git(path: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.current_dir(path);
+    command
+}
+
+/// Runs `command` and returns its output, or its error output when it
+/// fails.
+fn run(command: &mut Command) -> Result<String, String> {
+    let output = command
+        .output()
+        .map_err(|e| format!("Error running {:?}: {}", command, e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "{:?} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl Repository {
+    /// Checks whether a repository can be created at `path`, which must
+    /// exist. A new app inside an existing repository, e.g. a monorepo, is
+    /// left to it.
+    pub fn check(path: &Path) -> Result<(), Skipped> {
+        if run(git(path).arg("--version")).is_err() {
+            return Err(Skipped::GitMissing);
+        }
+
+        match run(git(path).args(["rev-parse", "--show-toplevel"])) {
+            Ok(root) => Err(Skipped::InsideRepository(PathBuf::from(root))),
+            Err(_) => Ok(()),
+        }
+    }
+
+    pub fn init(path: &Path) -> Result<Repository, String> {
+        run(git(path).arg("init"))?;
+
+        Ok(Repository {
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Stages every file and commits them. Returns `false` when there was
+    /// nothing to commit.
+    pub fn commit_all(&self, message: &str) -> Result<bool, String> {
+        run(git(&self.path).args(["add", "--all"]))?;
+
+        if run(git(&self.path).args(["diff", "--cached", "--quiet"])).is_ok() {
+            return Ok(false);
+        }
+
+        run(git(&self.path).args(["commit", "--quiet", "--no-verify", "-m", message]))?;
+
+        Ok(true)
+    }
+}
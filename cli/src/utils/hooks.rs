@@ -0,0 +1,124 @@
+#![deny(clippy::all)]
+
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+use super::fs::{read_json_file, read_template_json, Details, Hooks, TadaJson, TADA_JSON};
+use super::source::is_fetched;
+use crate::error::{Result, TadaError};
+
+/// Lines of output kept for a failed hook.
+const OUTPUT_TAIL: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    PostCopy,
+    PostInstall,
+}
+
+/// A command declared by the template or an addon.
+#[derive(Debug, Clone)]
+pub struct Hook {
+    /// `template <name>` or `addon <name>`.
+    pub origin: String,
+    pub stage: Stage,
+    pub command: String,
+    /// Declared by a local catalog root rather than a fetched one.
+    pub trusted: bool,
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.origin, self.command)
+    }
+}
+
+fn push_hooks(hooks: &mut Vec<Hook>, declared: Option<Hooks>, origin: String, details: &Details) {
+    let Some(declared) = declared else {
+        return;
+    };
+
+    let trusted = !is_fetched(Path::new(&details.root));
+
+    let stages = [
+        (Stage::PostCopy, declared.postCopy),
+        (Stage::PostInstall, declared.postInstall),
+    ];
+
+    for (stage, commands) in stages {
+        hooks.extend(commands.into_iter().map(|command| Hook {
+            origin: origin.clone(),
+            stage,
+            command,
+            trusted,
+        }));
+    }
+}
+
+/// Hooks of the template, then of every addon in the order they are
+/// applied.
+pub fn collect_hooks(template: &Details, addons: &[Details]) -> Result<Vec<Hook>> {
+    let mut hooks: Vec<Hook> = Vec::new();
+
+    let template_json = read_template_json(&template.path)?;
+    push_hooks(
+        &mut hooks,
+        template_json.hooks,
+        format!("template {}", template.name),
+        template,
+    );
+
+    for addon in addons {
+        let addon_tada_json_path = Path::new(&addon.path).join(TADA_JSON);
+        let addon_tada_json: TadaJson = read_json_file(addon_tada_json_path.as_os_str())?;
+        push_hooks(
+            &mut hooks,
+            addon_tada_json.hooks,
+            format!("addon {}", addon.name),
+            addon,
+        );
+    }
+
+    Ok(hooks)
+}
+
+fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    };
+    #[cfg(not(windows))]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    shell.arg(command);
+    shell
+}
+
+/// Runs `hook` in the new app directory. Its output is only shown when it
+/// fails.
+pub fn run_hook(hook: &Hook, app_path: &Path) -> Result<()> {
+    let output = shell(&hook.command)
+        .current_dir(app_path)
+        .output()
+        .map_err(TadaError::fs(format!("Error running `{}`", hook.command)))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let output = [output.stdout, output.stderr].concat();
+    let output = String::from_utf8_lossy(&output);
+    let lines = output.lines().collect::<Vec<_>>();
+
+    Err(TadaError::Hook {
+        command: hook.command.clone(),
+        output: lines[lines.len().saturating_sub(OUTPUT_TAIL)..].join("\n"),
+    })
+}
@@ -0,0 +1,115 @@
+#![deny(clippy::all)]
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{Result, TadaError};
+
+/// Set by Ctrl+C while an `Interrupt` is alive.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Catches Ctrl+C until it is dropped, then gives the previous handling
+/// back: the cli runs inside node, which keeps running afterwards.
+pub struct Interrupt {
+    #[cfg(unix)]
+    previous: libc::sigaction,
+}
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn on_interrupt(kind: u32) -> windows_sys::core::BOOL {
+    match kind {
+        windows_sys::Win32::System::Console::CTRL_C_EVENT => {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            1
+        }
+        _ => 0,
+    }
+}
+
+impl Interrupt {
+    pub fn catch() -> Result<Interrupt> {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+
+        let error = TadaError::fs("Error handling Ctrl+C");
+
+        #[cfg(unix)]
+        // SAFETY: the handler only stores to an atomic, and `previous` is
+        // filled by `sigaction` before being read.
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_interrupt as extern "C" fn(libc::c_int) as usize;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+
+            let mut previous: libc::sigaction = std::mem::zeroed();
+            if libc::sigaction(libc::SIGINT, &action, &mut previous) != 0 {
+                return Err(error(io::Error::last_os_error()));
+            }
+
+            Ok(Interrupt { previous })
+        }
+
+        #[cfg(windows)]
+        // SAFETY: the handler only stores to an atomic.
+        unsafe {
+            if windows_sys::Win32::System::Console::SetConsoleCtrlHandler(Some(on_interrupt), 1)
+                == 0
+            {
+                return Err(error(io::Error::last_os_error()));
+            }
+
+            Ok(Interrupt {})
+        }
+    }
+
+    pub fn interrupted(&self) -> bool {
+        INTERRUPTED.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Interrupt {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        // SAFETY: `previous` is the action replaced in `catch`.
+        unsafe {
+            libc::sigaction(libc::SIGINT, &self.previous, std::ptr::null_mut());
+        }
+
+        #[cfg(windows)]
+        // SAFETY: removes the handler added in `catch`.
+        unsafe {
+            windows_sys::Win32::System::Console::SetConsoleCtrlHandler(Some(on_interrupt), 0);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn current_handler() -> libc::sighandler_t {
+        unsafe {
+            let mut current: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(libc::SIGINT, std::ptr::null(), &mut current);
+            current.sa_sigaction
+        }
+    }
+
+    #[test]
+    fn the_previous_handler_is_restored() {
+        let before = current_handler();
+
+        let interrupt = Interrupt::catch().unwrap();
+        assert!(!interrupt.interrupted());
+        unsafe { libc::raise(libc::SIGINT) };
+        assert!(interrupt.interrupted());
+
+        drop(interrupt);
+        assert_eq!(current_handler(), before);
+    }
+}
@@ -1,2 +1,14 @@
+pub mod config;
+pub mod conflict;
+pub mod deps;
+pub mod diagnostic;
 pub mod fs;
+pub mod git;
+pub mod hooks;
+pub mod interrupt;
+pub mod name;
 pub mod pm;
+pub mod registry;
+pub mod source;
+pub mod target;
+pub mod workspace;
@@ -0,0 +1,234 @@
+#![deny(clippy::all)]
+
+/// Longest package name accepted by npm.
+const MAX_LENGTH: usize = 214;
+
+/// Node builtin modules, which npm refuses as names of new packages since
+/// `require` would load the builtin instead.
+const NODE_BUILTINS: [&str; 42] = [
+    "assert",
+    "async_hooks",
+    "buffer",
+    "child_process",
+    "cluster",
+    "console",
+    "constants",
+    "crypto",
+    "dgram",
+    "diagnostics_channel",
+    "dns",
+    "domain",
+    "events",
+    "fs",
+    "http",
+    "http2",
+    "https",
+    "inspector",
+    "module",
+    "net",
+    "os",
+    "path",
+    "perf_hooks",
+    "process",
+    "punycode",
+    "querystring",
+    "readline",
+    "repl",
+    "stream",
+    "string_decoder",
+    "sys",
+    "timers",
+    "tls",
+    "trace_events",
+    "tty",
+    "url",
+    "util",
+    "v8",
+    "vm",
+    "wasi",
+    "worker_threads",
+    "zlib",
+];
+
+/// Characters not allowed in Windows paths, refused everywhere so the app
+/// can be cloned on any platform.
+const INVALID_PATH_CHARS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+
+fn is_allowed(x: char) -> bool {
+    x.is_ascii_lowercase() || x.is_ascii_digit() || matches!(x, '-' | '.' | '_' | '~')
+}
+
+/// `(scope, name)` of a scoped package name like `@team/app`.
+fn split_scope(name: &str) -> Option<(&str, &str)> {
+    name.strip_prefix('@')?.split_once('/')
+}
+
+fn validate_part(part: &str) -> Result<(), String> {
+    if part.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+
+    if part.starts_with('.') || part.starts_with('_') {
+        return Err("must not start with `.` or `_`".to_string());
+    }
+
+    if let Some(x) = part.chars().find(|x| !is_allowed(*x)) {
+        return Err(format!(
+            "must only contain lowercase letters, digits, `-`, `.`, `_` or `~`, found `{}`",
+            x
+        ));
+    }
+
+    Ok(())
+}
+
+/// Rules npm applies to new package names, e.g. `app` or `@team/app`.
+pub fn validate_package_name(name: &str) -> Result<(), String> {
+    if name.len() > MAX_LENGTH {
+        return Err(format!(
+            "Package names must be at most {} characters long",
+            MAX_LENGTH
+        ));
+    }
+
+    if matches!(name, "node_modules" | "favicon.ico") {
+        return Err(format!("`{}` is not allowed as a package name", name));
+    }
+
+    if NODE_BUILTINS.contains(&name) {
+        return Err(format!(
+            "`{}` is a Node builtin module and cannot be a package name",
+            name
+        ));
+    }
+
+    let result = match split_scope(name) {
+        Some((scope, name)) => validate_part(scope).and_then(|_| validate_part(name)),
+        None if name.starts_with('@') => Err("must look like `@scope/name`".to_string()),
+        None => validate_part(name),
+    };
+
+    result.map_err(|e| format!("Package names {}", e))
+}
+
+/// Turns a directory name into a valid package name, e.g. `My App` into
+/// `my-app`. `None` when nothing usable is left.
+pub fn slugify(name: &str) -> Option<String> {
+    let mut slug = String::new();
+
+    for x in name.to_lowercase().chars() {
+        match x {
+            x if is_allowed(x) => slug.push(x),
+            _ if slug.ends_with('-') => {}
+            _ => slug.push('-'),
+        }
+    }
+
+    let slug = slug
+        .trim_start_matches(['.', '_', '-'])
+        .trim_end_matches('-')
+        .chars()
+        .take(MAX_LENGTH)
+        .collect::<String>();
+
+    validate_package_name(&slug).ok().map(|_| slug)
+}
+
+/// The project name given in the prompt: a path, or a scoped package name
+/// generated into a directory named after its unscoped part.
+pub fn validate_app_path(input: &str) -> Result<(), String> {
+    if input.trim().is_empty() {
+        return Err("Please enter a name or a path".to_string());
+    }
+
+    if input.starts_with('@') {
+        return validate_package_name(input);
+    }
+
+    // Keep the `:` of a drive, e.g. `C:\projects\app`.
+    let path = match cfg!(windows) && input.get(1..2) == Some(":") {
+        true => &input[2..],
+        false => input,
+    };
+
+    if let Some(x) = path
+        .chars()
+        .find(|x| INVALID_PATH_CHARS.contains(x) || x.is_control())
+    {
+        return Err(format!("Paths must not contain `{}`", x.escape_default()));
+    }
+
+    Ok(())
+}
+
+/// Directory the app is generated into for a prompt answer.
+pub fn app_dir(input: &str) -> &str {
+    match split_scope(input) {
+        Some((_, name)) => name,
+        None => input,
+    }
+}
+
+/// Package name of the app: the scoped name given in the prompt, otherwise
+/// the slugified directory name.
+pub fn package_name(input: &str, dir_name: Option<&str>) -> Option<String> {
+    if split_scope(input).is_some() && validate_package_name(input).is_ok() {
+        return Some(input.to_string());
+    }
+
+    dir_name.and_then(slugify)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directory_names_become_package_names() {
+        assert_eq!(slugify("My App").as_deref(), Some("my-app"));
+        assert_eq!(slugify("  Über__App!! ").as_deref(), Some("ber__app"));
+        assert_eq!(slugify("_.-app-").as_deref(), Some("app"));
+        assert_eq!(slugify("!!!"), None);
+        assert_eq!(slugify("__"), None);
+        assert_eq!(slugify("HTTP"), None);
+        assert_eq!(slugify(&"a".repeat(300)).map(|x| x.len()), Some(MAX_LENGTH));
+    }
+
+    #[test]
+    fn package_names_follow_npm() {
+        assert!(validate_package_name("app").is_ok());
+        assert!(validate_package_name("@team/app").is_ok());
+        assert!(validate_package_name("@team/http").is_ok());
+        assert!(validate_package_name(&"a".repeat(MAX_LENGTH)).is_ok());
+
+        assert!(validate_package_name(&"a".repeat(MAX_LENGTH + 1)).is_err());
+        assert!(validate_package_name("My-App").is_err());
+        assert!(validate_package_name("_app").is_err());
+        assert!(validate_package_name("@team").is_err());
+        assert!(validate_package_name("@team/").is_err());
+        assert!(validate_package_name("node_modules").is_err());
+        assert!(validate_package_name("fs").is_err());
+        assert!(validate_package_name("http").is_err());
+    }
+
+    #[test]
+    fn scoped_names_are_generated_into_their_unscoped_part() {
+        assert_eq!(app_dir("@team/app"), "app");
+        assert_eq!(app_dir("apps/web"), "apps/web");
+
+        assert_eq!(
+            package_name("@team/app", Some("app")).as_deref(),
+            Some("@team/app")
+        );
+        assert_eq!(
+            package_name("@Team/app", Some("app")).as_deref(),
+            Some("app")
+        );
+        assert_eq!(
+            package_name("apps/My App", Some("My App")).as_deref(),
+            Some("my-app")
+        );
+        assert_eq!(package_name("___", Some("___")), None);
+        assert_eq!(package_name("app", None), None);
+    }
+}
@@ -1,5 +1,7 @@
 use std::{path::PathBuf, process::Command};
 
+use crate::error::{Result, TadaError};
+
 fn pnpm() -> Command {
     #[cfg(windows)]
     const PNPM: &str = "pnpm.CMD";
@@ -27,17 +29,31 @@ fn npm() -> Command {
     Command::new(NPM)
 }
 
-pub fn install_dependencies(pm: &str, path: PathBuf) -> bool {
-    let mut command = match pm {
-        "pnpm" => pnpm(),
-        "yarn" => yarn(),
-        "npm" => npm(),
-        _ => panic!("Invalid package manager"),
-    };
+pub fn command(pm: &str) -> Result<Command> {
+    match pm {
+        "pnpm" => Ok(pnpm()),
+        "yarn" => Ok(yarn()),
+        "npm" => Ok(npm()),
+        _ => Err(TadaError::Install(format!(
+            "Invalid package manager `{}`",
+            pm
+        ))),
+    }
+}
 
-    if let Ok(status) = command.current_dir(path).arg("install").status() {
-        return status.success();
+pub fn install_dependencies(pm: &str, path: PathBuf) -> Result<()> {
+    let status = command(pm)?
+        .current_dir(path)
+        .arg("install")
+        .status()
+        .map_err(|e| TadaError::Install(format!("Error running `{} install`: {}", pm, e)))?;
+
+    if !status.success() {
+        return Err(TadaError::Install(format!(
+            "`{} install` failed with {}",
+            pm, status
+        )));
     }
 
-    return false;
+    Ok(())
 }
@@ -0,0 +1,464 @@
+#![deny(clippy::all)]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use ureq::{Agent, AgentBuilder};
+
+/// Prefixes of dependency specs that do not point to a registry version.
+const NON_REGISTRY_SPECS: [&str; 9] = [
+    "workspace:",
+    "file:",
+    "link:",
+    "portal:",
+    "npm:",
+    "git",
+    "http:",
+    "https:",
+    "github:",
+];
+
+#[derive(Debug)]
+pub struct ResolvedVersion {
+    pub name: String,
+    pub declared: String,
+    pub resolved: String,
+}
+
+#[derive(Debug, Default)]
+pub struct RegistryReport {
+    pub resolved: Vec<ResolvedVersion>,
+    /// Packages kept at their declared range, with the reason.
+    pub kept: Vec<(String, String)>,
+    /// Set once the registry could not be reached; every remaining
+    /// package keeps its declared range.
+    pub offline: bool,
+}
+
+#[derive(Debug)]
+pub enum RegistryError {
+    /// The registry answered with an error status, e.g. 404.
+    Status(u16),
+    /// The registry could not be reached.
+    Transport(String),
+    /// The registry answered with something else than dist-tags.
+    InvalidResponse(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::Status(code) => write!(f, "registry returned {}", code),
+            RegistryError::Transport(e) => write!(f, "{}", e),
+            RegistryError::InvalidResponse(e) => write!(f, "invalid registry response: {}", e),
+        }
+    }
+}
+
+pub struct Registry {
+    url: String,
+    agent: Agent,
+}
+
+impl Registry {
+    pub fn new(url: &str, timeout: Duration) -> Self {
+        Registry {
+            url: url.trim_end_matches('/').to_string(),
+            agent: AgentBuilder::new().timeout(timeout).build(),
+        }
+    }
+
+    /// Fetches the dist-tags of a package, e.g. `{ "latest": "4.1.4" }`.
+    pub fn dist_tags(&self, name: &str) -> Result<BTreeMap<String, String>, RegistryError> {
+        let url = format!(
+            "{}/-/package/{}/dist-tags",
+            self.url,
+            name.replace('/', "%2f")
+        );
+
+        let response = self.agent.get(&url).call().map_err(|e| match e {
+            ureq::Error::Status(code, _) => RegistryError::Status(code),
+            ureq::Error::Transport(e) => RegistryError::Transport(e.to_string()),
+        })?;
+
+        response
+            .into_json()
+            .map_err(|e| RegistryError::InvalidResponse(e.to_string()))
+    }
+
+    /// Rewrites every registry dependency in `dependencies` to the version
+    /// published under `dist_tag`. A declared spec that is itself a dist-tag
+    /// (e.g. `"beta"`) resolves that tag instead. Only `^x.y.z` and `~x.y.z`
+    /// ranges are rewritten, keeping their prefix; other ranges such as
+    /// `>=1.2.3 <2`, `1.x` or `*` are kept, a single version would narrow
+    /// them.
+    ///
+    /// Exact versions and prerelease ranges are kept unless `update_pinned`
+    /// is set, a dist-tag could otherwise downgrade them. Packages the
+    /// registry cannot resolve keep their declared range, and after the
+    /// first transport error the registry is treated as offline.
+    pub fn resolve(
+        &self,
+        dependencies: &mut BTreeMap<String, String>,
+        dist_tag: &str,
+        update_pinned: bool,
+        report: &mut RegistryReport,
+    ) {
+        for (name, declared) in dependencies.iter_mut() {
+            if report.offline {
+                report
+                    .kept
+                    .push((name.clone(), "registry offline".to_string()));
+                continue;
+            }
+
+            if NON_REGISTRY_SPECS
+                .iter()
+                .any(|prefix| declared.starts_with(prefix))
+            {
+                report
+                    .kept
+                    .push((name.clone(), "not a registry spec".to_string()));
+                continue;
+            }
+
+            if !update_pinned {
+                if let Some(reason) = pinned(declared) {
+                    report.kept.push((name.clone(), reason.to_string()));
+                    continue;
+                }
+            }
+
+            let prefix = range_prefix(declared);
+            if prefix.is_none() && !is_dist_tag(declared) {
+                report.kept.push((
+                    name.clone(),
+                    "only `^x.y.z` and `~x.y.z` ranges are rewritten".to_string(),
+                ));
+                continue;
+            }
+
+            let tags = match self.dist_tags(name) {
+                Ok(tags) => tags,
+                Err(e @ RegistryError::Transport(_)) => {
+                    report.offline = true;
+                    report.kept.push((name.clone(), e.to_string()));
+                    continue;
+                }
+                Err(e) => {
+                    report.kept.push((name.clone(), e.to_string()));
+                    continue;
+                }
+            };
+
+            let (tag, prefix) = match (tags.contains_key(declared.as_str()), prefix) {
+                (true, _) => (declared.as_str(), ""),
+                (false, Some(prefix)) => (dist_tag, prefix),
+                (false, None) => {
+                    report
+                        .kept
+                        .push((name.clone(), format!("no `{}` dist-tag", declared)));
+                    continue;
+                }
+            };
+
+            let version = match tags.get(tag) {
+                Some(version) => version,
+                None => {
+                    report
+                        .kept
+                        .push((name.clone(), format!("no `{}` dist-tag", tag)));
+                    continue;
+                }
+            };
+
+            let resolved = format!("{}{}", prefix, version);
+
+            if resolved != *declared {
+                report.resolved.push(ResolvedVersion {
+                    name: name.clone(),
+                    declared: declared.clone(),
+                    resolved: resolved.clone(),
+                });
+
+                *declared = resolved;
+            }
+        }
+    }
+}
+
+/// `1.2.3`, optionally with a prerelease or build part.
+fn is_version(version: &str) -> bool {
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    let parts = core.split('.').collect::<Vec<_>>();
+
+    parts.len() == 3
+        && parts
+            .iter()
+            .all(|x| !x.is_empty() && x.chars().all(|x| x.is_ascii_digit()))
+}
+
+/// Why a declared spec is kept as is: an exact version (`1.2.3`, `=1.2.3`)
+/// or a prerelease one (`1.0.0-beta.3`, `^1.0.0-rc.1`).
+fn pinned(declared: &str) -> Option<&'static str> {
+    let version = declared.trim_start_matches(['^', '~', '=', 'v']);
+
+    if !is_version(version) {
+        return None;
+    }
+
+    if version.split('+').next().unwrap_or_default().contains('-') {
+        return Some("prerelease version");
+    }
+
+    match range_prefix(declared) {
+        Some("") => Some("exact version"),
+        _ => None,
+    }
+}
+
+/// Prefix of the resolved version for the specs that can be rewritten:
+/// `^x.y.z` and `~x.y.z` keep theirs, exact versions have none.
+fn range_prefix(declared: &str) -> Option<&'static str> {
+    let (prefix, version) = match declared.chars().next() {
+        Some('^') => ("^", &declared[1..]),
+        Some('~') => ("~", &declared[1..]),
+        _ => ("", declared.trim_start_matches(['=', 'v'])),
+    };
+
+    is_version(version).then_some(prefix)
+}
+
+/// npm refuses dist-tags that are valid ranges, so a name that starts with
+/// a letter, other than the `x` wildcard, can only be a dist-tag.
+fn is_dist_tag(declared: &str) -> bool {
+    declared.starts_with(|x: char| x.is_ascii_alphabetic())
+        && !declared.eq_ignore_ascii_case("x")
+        && declared
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || matches!(x, '-' | '.' | '_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serves `responses`, `(path, status, body)`, until the test ends.
+    fn serve(responses: Vec<(&'static str, u16, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&stream);
+                reader.read_line(&mut request_line).unwrap();
+
+                // Skip the headers.
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 2 {
+                    line.clear();
+                }
+
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+                let (status, body) = responses
+                    .iter()
+                    .find(|(x, _, _)| *x == path)
+                    .map(|(_, status, body)| (*status, *body))
+                    .unwrap_or((404, "{}"));
+
+                let mut stream = stream;
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        url
+    }
+
+    fn dependencies(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(name, version)| (name.to_string(), version.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn range_prefix_keeps_caret_and_tilde() {
+        assert_eq!(range_prefix("^1.2.3"), Some("^"));
+        assert_eq!(range_prefix("~1.2.3"), Some("~"));
+        assert_eq!(range_prefix("1.2.3"), Some(""));
+        assert_eq!(range_prefix("=1.2.3"), Some(""));
+        assert_eq!(range_prefix("^1.2"), None);
+        assert_eq!(range_prefix(">=1.2.3"), None);
+        assert_eq!(range_prefix("1.x"), None);
+        assert_eq!(range_prefix(""), None);
+    }
+
+    #[test]
+    fn dist_tags_are_not_ranges() {
+        assert!(is_dist_tag("latest"));
+        assert!(is_dist_tag("next-15"));
+        assert!(!is_dist_tag("x"));
+        assert!(!is_dist_tag("*"));
+        assert!(!is_dist_tag("1.x"));
+        assert!(!is_dist_tag(">=1.2.3 <2"));
+        assert!(!is_dist_tag(""));
+    }
+
+    #[test]
+    fn pinned_specs() {
+        assert_eq!(pinned("1.2.3"), Some("exact version"));
+        assert_eq!(pinned("=1.2.3"), Some("exact version"));
+        assert_eq!(pinned("1.0.0-beta.3"), Some("prerelease version"));
+        assert_eq!(pinned("^2.0.0-rc.1"), Some("prerelease version"));
+        assert_eq!(pinned("1.2.3+build-5"), Some("exact version"));
+        assert_eq!(pinned("^1.2.3"), None);
+        assert_eq!(pinned("~1.2.3"), None);
+        assert_eq!(pinned("1.x"), None);
+        assert_eq!(pinned(">=1.2.3 <2"), None);
+        assert_eq!(pinned("beta"), None);
+    }
+
+    #[test]
+    fn resolve_rewrites_ranges_and_keeps_pins() {
+        let url = serve(vec![
+            (
+                "/-/package/vite/dist-tags",
+                200,
+                r#"{"latest":"6.1.0","beta":"7.0.0-beta.1"}"#,
+            ),
+            ("/-/package/react/dist-tags", 200, r#"{"latest":"19.1.0"}"#),
+            (
+                "/-/package/@types%2freact/dist-tags",
+                200,
+                r#"{"latest":"19.1.2"}"#,
+            ),
+        ]);
+        let registry = Registry::new(&url, Duration::from_secs(5));
+        let mut report = RegistryReport::default();
+
+        let mut deps = dependencies(&[
+            ("vite", "beta"),
+            ("react", "^18.0.0"),
+            ("@types/react", "~18.3.0"),
+            ("pinned", "1.0.0-beta.3"),
+            ("local", "workspace:*"),
+        ]);
+        registry.resolve(&mut deps, "latest", false, &mut report);
+
+        assert_eq!(
+            deps,
+            dependencies(&[
+                ("vite", "7.0.0-beta.1"),
+                ("react", "^19.1.0"),
+                ("@types/react", "~19.1.2"),
+                ("pinned", "1.0.0-beta.3"),
+                ("local", "workspace:*"),
+            ])
+        );
+        assert_eq!(report.resolved.len(), 3);
+        assert!(!report.offline);
+    }
+
+    #[test]
+    fn other_ranges_are_kept_with_a_reason() {
+        let url = serve(vec![(
+            "/-/package/react/dist-tags",
+            200,
+            r#"{"latest":"19.1.0"}"#,
+        )]);
+        let registry = Registry::new(&url, Duration::from_secs(5));
+        let mut report = RegistryReport::default();
+
+        let declared = dependencies(&[
+            ("bounded", ">=1.2.3 <2"),
+            ("minor", "1.x"),
+            ("any", "*"),
+            ("react", "canary"),
+        ]);
+        let mut deps = declared.clone();
+        registry.resolve(&mut deps, "latest", true, &mut report);
+
+        assert_eq!(deps, declared);
+        assert!(report.resolved.is_empty());
+        assert_eq!(report.kept.len(), 4);
+        assert!(report.kept.iter().any(|(name, reason)| name == "minor"
+            && reason == "only `^x.y.z` and `~x.y.z` ranges are rewritten"));
+        assert!(report
+            .kept
+            .iter()
+            .any(|(name, reason)| name == "react" && reason == "no `canary` dist-tag"));
+    }
+
+    #[test]
+    fn resolve_updates_pins_when_asked() {
+        let url = serve(vec![(
+            "/-/package/react/dist-tags",
+            200,
+            r#"{"latest":"19.1.0"}"#,
+        )]);
+        let registry = Registry::new(&url, Duration::from_secs(5));
+        let mut report = RegistryReport::default();
+
+        let mut deps = dependencies(&[("react", "19.0.0-rc.1")]);
+        registry.resolve(&mut deps, "latest", true, &mut report);
+
+        assert_eq!(deps, dependencies(&[("react", "19.1.0")]));
+    }
+
+    #[test]
+    fn bad_responses_keep_the_registry_online() {
+        let url = serve(vec![
+            ("/-/package/broken/dist-tags", 200, "not json"),
+            ("/-/package/react/dist-tags", 200, r#"{"latest":"19.1.0"}"#),
+        ]);
+        let registry = Registry::new(&url, Duration::from_secs(5));
+        let mut report = RegistryReport::default();
+
+        let mut deps = dependencies(&[
+            ("broken", "^1.0.0"),
+            ("missing", "^1.0.0"),
+            ("react", "^18.0.0"),
+        ]);
+        registry.resolve(&mut deps, "latest", false, &mut report);
+
+        assert!(!report.offline);
+        assert_eq!(deps["react"], "^19.1.0");
+        assert_eq!(deps["broken"], "^1.0.0");
+        assert_eq!(deps["missing"], "^1.0.0");
+        assert!(report
+            .kept
+            .iter()
+            .any(|(name, reason)| name == "missing" && reason == "registry returned 404"));
+    }
+
+    #[test]
+    fn unreachable_registry_is_offline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let registry = Registry::new(&url, Duration::from_secs(5));
+        let mut report = RegistryReport::default();
+
+        let mut deps = dependencies(&[("react", "^18.0.0"), ("vite", "^5.0.0")]);
+        registry.resolve(&mut deps, "latest", false, &mut report);
+
+        assert!(report.offline);
+        assert_eq!(
+            deps,
+            dependencies(&[("react", "^18.0.0"), ("vite", "^5.0.0")])
+        );
+        assert_eq!(report.kept.len(), 2);
+    }
+}
@@ -0,0 +1,565 @@
+#![deny(clippy::all)]
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use flate2::read::GzDecoder;
+
+use crate::error::{Result, TadaError};
+
+const ARCHIVE_EXTENSIONS: [&str; 3] = [".tar.gz", ".tgz", ".zip"];
+
+/// Where a catalog root comes from.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Source {
+    Local(PathBuf),
+    /// A git repository, e.g. `git+https://host/catalog.git#v1`.
+    Git {
+        url: String,
+        reference: Option<String>,
+    },
+    /// A `.tar.gz`/`.tgz`/`.zip` file, given as a path or an URL, optionally
+    /// followed by a `#fragment`.
+    Archive {
+        location: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct Fetched {
+    pub path: PathBuf,
+    /// Set when a stale cached copy is used because fetching failed.
+    pub warning: Option<String>,
+}
+
+impl Source {
+    pub fn parse(spec: &str) -> Source {
+        let (location, reference) = match spec.rsplit_once('#') {
+            Some((location, reference)) if !reference.is_empty() => {
+                (location, Some(reference.to_string()))
+            }
+            _ => (spec, None),
+        };
+
+        if let Some(url) = location.strip_prefix("git+") {
+            return Source::Git {
+                url: url.to_string(),
+                reference,
+            };
+        }
+
+        if location.ends_with(".git") {
+            return Source::Git {
+                url: location.to_string(),
+                reference,
+            };
+        }
+
+        // The fragment stays in the location of archives, so that
+        // `catalog.tar.gz#v2` gets its own cache entry.
+        if ARCHIVE_EXTENSIONS.iter().any(|x| location.ends_with(x)) {
+            return Source::Archive {
+                location: spec.to_string(),
+            };
+        }
+
+        Source::Local(PathBuf::from(spec))
+    }
+
+    /// Makes the source available on disk and returns the catalog root.
+    ///
+    /// When `offline` is set, git repositories and remote archives are only
+    /// read from the cache. `timeout` bounds the download of an archive.
+    pub fn fetch(&self, offline: bool, timeout: Duration) -> Result<Fetched> {
+        match self {
+            Source::Local(path) => Ok(Fetched {
+                path: path.clone(),
+                warning: None,
+            }),
+            Source::Git { url, reference } => fetch_git(url, reference.as_deref(), offline),
+            Source::Archive { location } => fetch_archive(location, offline, timeout),
+        }
+    }
+}
+
+/// Splits a list of roots separated like `PATH`. On Unix, the `:` of an
+/// URL scheme (`https://...`) is not treated as a separator.
+pub fn split_roots(value: &str) -> Vec<String> {
+    #[cfg(windows)]
+    const SEPARATOR: char = ';';
+    #[cfg(not(windows))]
+    const SEPARATOR: char = ':';
+
+    let mut roots: Vec<String> = Vec::new();
+
+    for part in value.split(SEPARATOR) {
+        match roots.last_mut() {
+            Some(last) if part.starts_with("//") => {
+                last.push(SEPARATOR);
+                last.push_str(part);
+            }
+            _ => roots.push(part.to_string()),
+        }
+    }
+
+    roots.retain(|x| !x.is_empty());
+
+    roots
+}
+
+/// Cache for fetched catalogs, under `$XDG_CACHE_HOME/tada-app`
+/// (`~/.cache` when unset, `%LOCALAPPDATA%` on Windows).
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            if cfg!(windows) {
+                env::var_os("LOCALAPPDATA").map(PathBuf::from)
+            } else {
+                env::var_os("HOME").map(|home| Path::new(&home).join(".cache"))
+            }
+        })?;
+
+    Some(cache_home.join("tada-app"))
+}
+
+/// Git repositories and archives are extracted into the cache, unlike local
+/// roots which are used in place.
+pub fn is_fetched(root: &Path) -> bool {
+    cache_dir().is_some_and(|cache_dir| root.starts_with(cache_dir))
+}
+
+/// 64-bit FNV-1a, stable across runs and Rust versions unlike `DefaultHasher`.
+fn hash(key: &str) -> u64 {
+    key.bytes().fold(0xcbf29ce484222325, |hash, x| {
+        (hash ^ u64::from(x)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Cache entry of a source: the last segment of `key` to keep it readable,
+/// then a hash of the whole key so that different sources never share one.
+fn cache_path(kind: &str, key: &str) -> Result<PathBuf> {
+    let name = key
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\', ':'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|x| match x.is_ascii_alphanumeric() || x == '.' {
+            true => x,
+            false => '-',
+        })
+        .take(32)
+        .collect::<String>();
+
+    let cache_dir = cache_dir()
+        .ok_or_else(|| TadaError::Catalog("Unable to locate the cache directory".to_string()))?;
+
+    Ok(cache_dir
+        .join(kind)
+        .join(format!("{}-{:016x}", name.trim_matches('-'), hash(key))))
+}
+
+fn git() -> Command {
+    Command::new("git")
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let output = command
+        .output()
+        .map_err(TadaError::fs(format!("Error running {:?}", command)))?;
+
+    if !output.status.success() {
+        return Err(TadaError::Catalog(format!(
+            "{:?} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+fn fetch_git(url: &str, reference: Option<&str>, offline: bool) -> Result<Fetched> {
+    let key = match reference {
+        Some(reference) => format!("{}#{}", url, reference),
+        None => url.to_string(),
+    };
+    let path = cache_path("git", &key)?;
+    let cached = path.join(".git").is_dir();
+
+    if offline {
+        if !cached {
+            return Err(TadaError::Catalog(format!(
+                "{} is not in the cache, run again without --offline",
+                key
+            )));
+        }
+
+        return Ok(Fetched {
+            path,
+            warning: None,
+        });
+    }
+
+    let fetched = (|| -> Result<()> {
+        if !cached {
+            fs::create_dir_all(&path).map_err(TadaError::fs(format!(
+                "Error creating directory: {:?}",
+                path
+            )))?;
+            run(git().arg("init").arg("--quiet").arg(&path))?;
+            run(git()
+                .arg("-C")
+                .arg(&path)
+                .args(["remote", "add", "origin", url]))?;
+        }
+
+        run(git().arg("-C").arg(&path).args([
+            "fetch",
+            "--quiet",
+            "--depth",
+            "1",
+            "origin",
+            reference.unwrap_or("HEAD"),
+        ]))?;
+        run(git()
+            .arg("-C")
+            .arg(&path)
+            .args(["checkout", "--quiet", "--force", "FETCH_HEAD"]))?;
+
+        Ok(())
+    })();
+
+    match fetched {
+        Ok(_) => Ok(Fetched {
+            path,
+            warning: None,
+        }),
+        Err(e) if cached => Ok(Fetched {
+            path,
+            warning: Some(format!("{}, using the cached copy", e)),
+        }),
+        Err(e) => {
+            let _ = fs::remove_dir_all(&path);
+            Err(TadaError::Catalog(format!("Error fetching {}: {}", key, e)))
+        }
+    }
+}
+
+fn fetch_archive(location: &str, offline: bool, timeout: Duration) -> Result<Fetched> {
+    let remote = location.starts_with("http://") || location.starts_with("https://");
+    let file = location.split_once('#').map_or(location, |(file, _)| file);
+    let path = cache_path("archives", location)?;
+
+    if remote && offline {
+        if !path.is_dir() {
+            return Err(TadaError::Catalog(format!(
+                "{} is not in the cache, run again without --offline",
+                location
+            )));
+        }
+
+        return Ok(Fetched {
+            path: archive_root(&path)?,
+            warning: None,
+        });
+    }
+
+    let extracted = (|| -> Result<()> {
+        let reader: Box<dyn io::Read> = if remote {
+            let response = ureq::AgentBuilder::new()
+                .timeout(timeout)
+                .build()
+                .get(location)
+                .call()
+                .map_err(|e| {
+                    TadaError::Catalog(format!("Error downloading {}: {}", location, e))
+                })?;
+
+            Box::new(response.into_reader())
+        } else {
+            let file_path = file.strip_prefix("file://").unwrap_or(file);
+            Box::new(
+                fs::File::open(file_path)
+                    .map_err(TadaError::fs(format!("Error reading {}", file_path)))?,
+            )
+        };
+
+        // Extract next to the cache entry first, so a failed download
+        // never replaces a good copy.
+        let tmp_path = path.with_extension("tmp");
+        let _ = fs::remove_dir_all(&tmp_path);
+        fs::create_dir_all(&tmp_path).map_err(TadaError::fs(format!(
+            "Error creating directory: {:?}",
+            tmp_path
+        )))?;
+
+        if file.ends_with(".zip") {
+            let mut bytes: Vec<u8> = Vec::new();
+            io::Read::read_to_end(&mut io::BufReader::new(reader), &mut bytes)
+                .map_err(TadaError::fs(format!("Error reading {}", location)))?;
+            zip::ZipArchive::new(io::Cursor::new(bytes))
+                .and_then(|mut archive| archive.extract(&tmp_path))
+                .map_err(TadaError::fs(format!("Error extracting {}", location)))?;
+        } else {
+            tar::Archive::new(GzDecoder::new(reader))
+                .unpack(&tmp_path)
+                .map_err(TadaError::fs(format!("Error extracting {}", location)))?;
+        }
+
+        let _ = fs::remove_dir_all(&path);
+        fs::rename(&tmp_path, &path)
+            .map_err(TadaError::fs(format!("Error moving {:?}", tmp_path)))?;
+
+        Ok(())
+    })();
+
+    match extracted {
+        Ok(_) => Ok(Fetched {
+            path: archive_root(&path)?,
+            warning: None,
+        }),
+        Err(e) if path.is_dir() => Ok(Fetched {
+            path: archive_root(&path)?,
+            warning: Some(format!("{}, using the cached copy", e)),
+        }),
+        Err(e) => Err(TadaError::Catalog(format!(
+            "Error fetching {}: {}",
+            location, e
+        ))),
+    }
+}
+
+/// Directories of a catalog root, never taken for a wrapping directory.
+const CATALOG_DIRS: [&str; 4] = ["templates", "addons", "presets", "snapshots"];
+
+/// Archives such as the ones generated by git hosts wrap everything in a
+/// single top-level directory, which is then used as the catalog root.
+fn archive_root(path: &Path) -> Result<PathBuf> {
+    let entries = fs::read_dir(path)
+        .map_err(TadaError::fs(format!("Error reading {:?}", path)))?
+        .flatten()
+        .map(|x| x.path())
+        .collect::<Vec<_>>();
+
+    match entries.as_slice() {
+        [single]
+            if single.is_dir()
+                && !single
+                    .file_name()
+                    .is_some_and(|x| CATALOG_DIRS.iter().any(|dir| x == *dir)) =>
+        {
+            Ok(single.clone())
+        }
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Once;
+
+    /// Every fetch test shares one cache, the keys keep them apart.
+    fn use_temp_cache() {
+        static CACHE: Once = Once::new();
+
+        CACHE.call_once(|| {
+            let cache = tempfile::tempdir().unwrap().keep();
+            env::set_var("XDG_CACHE_HOME", cache);
+        });
+    }
+
+    fn write_catalog(path: &Path) {
+        let template = path.join("templates/react");
+        fs::create_dir_all(&template).unwrap();
+        fs::write(template.join("package.json"), "{}").unwrap();
+    }
+
+    #[test]
+    fn parse_sources() {
+        assert_eq!(
+            Source::parse("git+https://host/catalog.git#v1"),
+            Source::Git {
+                url: "https://host/catalog.git".to_string(),
+                reference: Some("v1".to_string()),
+            }
+        );
+        assert_eq!(
+            Source::parse("https://host/catalog.git"),
+            Source::Git {
+                url: "https://host/catalog.git".to_string(),
+                reference: None,
+            }
+        );
+        assert_eq!(
+            Source::parse("https://host/catalog.tar.gz"),
+            Source::Archive {
+                location: "https://host/catalog.tar.gz".to_string(),
+            }
+        );
+        assert_eq!(
+            Source::parse("https://host/catalog.tar.gz#v1"),
+            Source::Archive {
+                location: "https://host/catalog.tar.gz#v1".to_string(),
+            }
+        );
+        assert_eq!(
+            Source::parse("./catalog.zip"),
+            Source::Archive {
+                location: "./catalog.zip".to_string(),
+            }
+        );
+        assert_eq!(
+            Source::parse("/path/to/catalog"),
+            Source::Local(PathBuf::from("/path/to/catalog"))
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn split_roots_keeps_urls_whole() {
+        assert_eq!(
+            split_roots("/personal:git+https://host/team.git#main::https://host/a.zip"),
+            [
+                "/personal",
+                "git+https://host/team.git#main",
+                "https://host/a.zip"
+            ]
+        );
+        assert!(split_roots("").is_empty());
+    }
+
+    #[test]
+    fn cache_paths_do_not_collide() {
+        use_temp_cache();
+
+        let keys = ["a/b", "a-b", "https://x/y", "http-x-y", "https://x/y#main"];
+        let mut paths = keys
+            .iter()
+            .map(|key| cache_path("git", key).unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(paths[2]
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("y-"));
+
+        paths.sort();
+        paths.dedup();
+        assert_eq!(paths.len(), keys.len());
+    }
+
+    #[test]
+    fn fetch_git_repository() {
+        use_temp_cache();
+
+        let repository = tempfile::tempdir().unwrap();
+        write_catalog(repository.path());
+
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(repository.path())
+                .args(["-c", "user.name=tada", "-c", "user.email=tada@localhost"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        git(&["init", "--quiet", "--initial-branch", "main"]);
+        git(&["add", "--all"]);
+        git(&["commit", "--quiet", "-m", "Catalog"]);
+
+        let spec = format!("git+file://{}#main", repository.path().display());
+        let fetched = Source::parse(&spec)
+            .fetch(false, Duration::from_secs(5))
+            .unwrap();
+
+        assert!(fetched.warning.is_none());
+        assert!(fetched.path.join("templates/react/package.json").is_file());
+        assert!(is_fetched(&fetched.path));
+
+        let cached = Source::parse(&spec)
+            .fetch(true, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(cached.path, fetched.path);
+
+        let missing = format!(
+            "git+file://{}#main",
+            repository.path().join("missing").display()
+        );
+        assert!(Source::parse(&missing)
+            .fetch(false, Duration::from_secs(5))
+            .is_err());
+    }
+
+    #[test]
+    fn fetch_local_archives() {
+        use_temp_cache();
+
+        let dir = tempfile::tempdir().unwrap();
+        let catalog = dir.path().join("catalog-main");
+        write_catalog(&catalog);
+
+        let tar_gz = dir.path().join("catalog.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            fs::File::create(&tar_gz).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut archive = tar::Builder::new(encoder);
+        archive.append_dir_all("catalog-main", &catalog).unwrap();
+        archive.into_inner().unwrap().finish().unwrap();
+
+        let zip_path = dir.path().join("catalog.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        zip.start_file(
+            "templates/react/package.json",
+            zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+        io::Write::write_all(&mut zip, b"{}").unwrap();
+        zip.finish().unwrap();
+
+        for (archive, root) in [(&tar_gz, "catalog-main"), (&zip_path, "")] {
+            let spec = archive.to_string_lossy().to_string();
+            let fetched = Source::parse(&spec)
+                .fetch(false, Duration::from_secs(5))
+                .unwrap();
+
+            assert!(fetched.warning.is_none());
+            assert!(fetched.path.ends_with(root) || root.is_empty());
+            assert!(fetched.path.join("templates/react/package.json").is_file());
+        }
+
+        // The fragment is left out of the file name, but not of the cache.
+        let spec = zip_path.to_string_lossy().to_string();
+        let fetched = Source::parse(&format!("{}#v1", spec))
+            .fetch(false, Duration::from_secs(5))
+            .unwrap();
+        assert!(fetched.path.join("templates/react/package.json").is_file());
+        assert_ne!(
+            fetched.path,
+            Source::parse(&spec)
+                .fetch(false, Duration::from_secs(5))
+                .unwrap()
+                .path
+        );
+
+        // A broken archive falls back to the cached copy.
+        fs::write(&tar_gz, "not an archive").unwrap();
+        let fetched = Source::parse(&tar_gz.to_string_lossy())
+            .fetch(false, Duration::from_secs(5))
+            .unwrap();
+        assert!(fetched.warning.is_some());
+        assert!(fetched.path.join("templates/react/package.json").is_file());
+    }
+}
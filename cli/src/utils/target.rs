@@ -0,0 +1,60 @@
+#![deny(clippy::all)]
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What to do with a target directory that is not empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExistingTarget {
+    Cancel,
+    /// Remove its content first.
+    Empty,
+    /// Generate over its content.
+    Merge,
+}
+
+fn home_dir() -> Option<PathBuf> {
+    let home = match cfg!(windows) {
+        true => env::var_os("USERPROFILE"),
+        false => env::var_os("HOME"),
+    };
+
+    home.filter(|x| !x.is_empty()).map(PathBuf::from)
+}
+
+/// The home directory and the filesystem root are never generated into,
+/// emptied or merged with.
+pub fn is_protected(path: &Path) -> bool {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    if path.parent().is_none() {
+        return true;
+    }
+
+    home_dir()
+        .map(|home| home.canonicalize().unwrap_or(home))
+        .is_some_and(|home| home == path)
+}
+
+pub fn is_non_empty(path: &Path) -> bool {
+    fs::read_dir(path)
+        .map(|mut entries| entries.next().is_some())
+        .unwrap_or(false)
+}
+
+/// Removes everything inside `path`, but not `path` itself.
+pub fn empty_dir(path: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+
+        if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,364 @@
+#![deny(clippy::all)]
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use globset::{GlobBuilder, GlobSetBuilder};
+use serde_json::Value;
+
+/// Lockfiles a template must not bring into a workspace.
+pub const LOCKFILES: [&str; 4] = [
+    "pnpm-lock.yaml",
+    "package-lock.json",
+    "yarn.lock",
+    "bun.lockb",
+];
+
+/// Globs a bare app name is placed under, in order of preference.
+const APP_GLOBS: [&str; 2] = ["apps/*", "packages/*"];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum WorkspaceKind {
+    /// Packages listed in `pnpm-workspace.yaml`.
+    Pnpm,
+    /// Packages listed in the `workspaces` field of the root `package.json`.
+    PackageJson,
+}
+
+#[derive(Debug)]
+pub struct Workspace {
+    pub root: PathBuf,
+    pub kind: WorkspaceKind,
+    pub packages: Vec<String>,
+}
+
+impl Workspace {
+    /// Walks up from `path` looking for a workspace root.
+    pub fn find(path: &Path) -> Option<Workspace> {
+        for dir in path.ancestors() {
+            let pnpm_workspace = dir.join("pnpm-workspace.yaml");
+            if pnpm_workspace.is_file() {
+                let content = fs::read_to_string(&pnpm_workspace).ok()?;
+
+                return Some(Workspace {
+                    root: dir.to_path_buf(),
+                    kind: WorkspaceKind::Pnpm,
+                    packages: read_pnpm_packages(&content),
+                });
+            }
+
+            let package_json = dir.join("package.json");
+            if package_json.is_file() {
+                let content = fs::read_to_string(&package_json).ok()?;
+                let value: Value = serde_json::from_str(&content).ok()?;
+
+                if let Some(packages) = read_package_json_workspaces(&value) {
+                    return Some(Workspace {
+                        root: dir.to_path_buf(),
+                        kind: WorkspaceKind::PackageJson,
+                        packages,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Path of `app_path` relative to the workspace root, `/`-separated.
+    pub fn relative(&self, app_path: &Path) -> Option<String> {
+        let relative = app_path.strip_prefix(&self.root).ok()?;
+
+        let parts = relative
+            .components()
+            .map(|component| match component {
+                Component::Normal(part) => part.to_str().map(|x| x.to_string()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(parts.join("/"))
+    }
+
+    /// Whether `app_path` is already covered by the workspace packages.
+    pub fn contains(&self, app_path: &Path) -> bool {
+        let relative = match self.relative(app_path) {
+            Some(relative) => relative,
+            None => return false,
+        };
+
+        let mut included = GlobSetBuilder::new();
+        let mut excluded = GlobSetBuilder::new();
+
+        for pattern in &self.packages {
+            let (builder, pattern) = match pattern.strip_prefix('!') {
+                Some(pattern) => (&mut excluded, pattern),
+                None => (&mut included, pattern.as_str()),
+            };
+
+            let glob = GlobBuilder::new(pattern.trim_start_matches("./"))
+                .literal_separator(true)
+                .build();
+
+            if let Ok(glob) = glob {
+                builder.add(glob);
+            }
+        }
+
+        match (included.build(), excluded.build()) {
+            (Ok(included), Ok(excluded)) => {
+                included.is_match(&relative) && !excluded.is_match(&relative)
+            }
+            _ => false,
+        }
+    }
+
+    /// Directory a bare app name should be generated in, taken from the
+    /// first of `apps/*` or `packages/*` listed by the workspace.
+    pub fn apps_dir(&self) -> Option<PathBuf> {
+        APP_GLOBS
+            .iter()
+            .find(|glob| {
+                self.packages
+                    .iter()
+                    .any(|pattern| pattern.trim_start_matches("./") == **glob)
+            })
+            .map(|glob| self.root.join(glob.trim_end_matches("/*")))
+    }
+
+    /// Adds `app_path` to the workspace packages. Returns `false` when the
+    /// packages list could not be found, e.g. a `workspaces` object without
+    /// `packages`, and nothing was written.
+    pub fn register(&self, app_path: &Path) -> io::Result<bool> {
+        let relative = self.relative(app_path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} is not inside {:?}", app_path, self.root),
+            )
+        })?;
+
+        match self.kind {
+            WorkspaceKind::Pnpm => {
+                let path = self.root.join("pnpm-workspace.yaml");
+                let content = fs::read_to_string(&path)?;
+
+                fs::write(path, add_pnpm_package(&content, &relative))?;
+                Ok(true)
+            }
+            WorkspaceKind::PackageJson => {
+                let path = self.root.join("package.json");
+                let content = fs::read_to_string(&path)?;
+                let mut value: Value = serde_json::from_str(&content)?;
+
+                let workspaces = match value.get_mut("workspaces") {
+                    Some(Value::Object(map)) => map.get_mut("packages"),
+                    other => other,
+                };
+
+                let Some(Value::Array(packages)) = workspaces else {
+                    return Ok(false);
+                };
+                packages.push(Value::String(relative));
+
+                fs::write(path, serde_json::to_string_pretty(&value)? + "\n")?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Package manager used to install from the workspace root.
+    pub fn package_manager(&self) -> &'static str {
+        match self.kind {
+            WorkspaceKind::Pnpm => "pnpm",
+            WorkspaceKind::PackageJson => {
+                if self.root.join("yarn.lock").exists() {
+                    "yarn"
+                } else {
+                    "npm"
+                }
+            }
+        }
+    }
+}
+
+/// `line` without its trailing `# comment`, a `#` inside quotes is kept.
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut previous = ' ';
+
+    for (index, x) in line.char_indices() {
+        match quote {
+            Some(q) if x == q => quote = None,
+            Some(_) => {}
+            None if x == '"' || x == '\'' => quote = Some(x),
+            None if x == '#' && previous.is_whitespace() => return &line[..index],
+            None => {}
+        }
+        previous = x;
+    }
+
+    line
+}
+
+fn read_pnpm_packages(content: &str) -> Vec<String> {
+    let mut packages: Vec<String> = Vec::new();
+    let mut in_packages = false;
+
+    for line in content.lines() {
+        let line = strip_comment(line);
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        if !line.starts_with([' ', '\t', '-']) {
+            in_packages = trimmed == "packages:";
+            continue;
+        }
+
+        if in_packages {
+            if let Some(item) = trimmed.strip_prefix('-') {
+                packages.push(unquote(item.trim()).to_string());
+            }
+        }
+    }
+
+    packages
+}
+
+fn add_pnpm_package(content: &str, relative: &str) -> String {
+    let lines = content.lines().collect::<Vec<_>>();
+    let item = format!("  - \"{}\"", relative);
+
+    let start = match lines
+        .iter()
+        .position(|line| strip_comment(line).trim_end() == "packages:")
+    {
+        Some(start) => start,
+        None => {
+            let mut content = content.to_string();
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            return format!("{}packages:\n{}\n", content, item);
+        }
+    };
+
+    let mut end = start + 1;
+    while end < lines.len()
+        && (lines[end].trim().is_empty() || lines[end].starts_with([' ', '\t', '-']))
+    {
+        end += 1;
+    }
+    while end > start + 1 && lines[end - 1].trim().is_empty() {
+        end -= 1;
+    }
+
+    let mut result = lines[..end].to_vec();
+    result.push(&item);
+    result.extend_from_slice(&lines[end..]);
+
+    result.join("\n") + "\n"
+}
+
+fn read_package_json_workspaces(value: &Value) -> Option<Vec<String>> {
+    let workspaces = match value.get("workspaces")? {
+        Value::Object(map) => map.get("packages")?,
+        other => other,
+    };
+
+    let packages = workspaces
+        .as_array()?
+        .iter()
+        .filter_map(|x| x.as_str().map(|x| x.to_string()))
+        .collect();
+
+    Some(packages)
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix(['"', '\''])
+        .and_then(|x| x.strip_suffix(['"', '\'']))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pnpm_packages_without_comments() {
+        let content = r#"# the apps
+packages: # every package
+  - "apps/*" # the apps
+  - 'packages/#internal'
+  - tools/*
+  # - "docs"
+catalog:
+  react: ^19.0.0
+"#;
+
+        assert_eq!(
+            read_pnpm_packages(content),
+            ["apps/*", "packages/#internal", "tools/*"]
+        );
+    }
+
+    #[test]
+    fn pnpm_package_added_after_the_list() {
+        let content = "packages: # apps\n  - \"apps/*\"\n\ncatalog:\n  react: ^19.0.0\n";
+
+        assert_eq!(
+            add_pnpm_package(content, "tools/app"),
+            "packages: # apps\n  - \"apps/*\"\n  - \"tools/app\"\n\ncatalog:\n  react: ^19.0.0\n"
+        );
+        assert_eq!(
+            add_pnpm_package("catalog: {}", "app"),
+            "catalog: {}\npackages:\n  - \"app\"\n"
+        );
+    }
+
+    #[test]
+    fn contains_follows_globs_and_negations() {
+        let workspace = Workspace {
+            root: PathBuf::from("/repo"),
+            kind: WorkspaceKind::Pnpm,
+            packages: vec!["apps/*".to_string(), "!apps/legacy".to_string()],
+        };
+
+        assert!(workspace.contains(Path::new("/repo/apps/web")));
+        assert!(!workspace.contains(Path::new("/repo/apps/legacy")));
+        assert!(!workspace.contains(Path::new("/repo/apps/web/nested")));
+        assert!(!workspace.contains(Path::new("/elsewhere/apps/web")));
+        assert_eq!(workspace.apps_dir(), Some(PathBuf::from("/repo/apps")));
+    }
+
+    #[test]
+    fn register_reports_missing_packages() {
+        let root = tempfile::tempdir().unwrap();
+        let package_json = root.path().join("package.json");
+
+        fs::write(&package_json, r#"{"workspaces": {"packages": ["apps/*"]}}"#).unwrap();
+        let workspace = Workspace::find(root.path()).unwrap();
+        assert!(workspace.register(&root.path().join("tools/app")).unwrap());
+        assert_eq!(
+            read_package_json_workspaces(
+                &serde_json::from_str(&fs::read_to_string(&package_json).unwrap()).unwrap()
+            ),
+            Some(vec!["apps/*".to_string(), "tools/app".to_string()])
+        );
+
+        let written = r#"{"workspaces": {"nohoist": ["**/react"]}}"#;
+        fs::write(&package_json, written).unwrap();
+        let workspace = Workspace {
+            root: root.path().to_path_buf(),
+            kind: WorkspaceKind::PackageJson,
+            packages: Vec::new(),
+        };
+        assert!(!workspace.register(&root.path().join("tools/app")).unwrap());
+        assert_eq!(fs::read_to_string(&package_json).unwrap(), written);
+    }
+}